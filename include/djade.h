@@ -0,0 +1,92 @@
+#ifndef DJADE_H
+#define DJADE_H
+
+/* Generated with cbindgen from src/capi.rs. Do not edit by hand. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Match the template’s first newline.
+ */
+#define DJADE_NEWLINE_AUTO 0
+
+/**
+ * Use `\n` for all newlines.
+ */
+#define DJADE_NEWLINE_LF 1
+
+/**
+ * Use `\r\n` for all newlines.
+ */
+#define DJADE_NEWLINE_CRLF 2
+
+/**
+ * Formatting succeeded.
+ */
+#define DJADE_OK 0
+
+/**
+ * A required pointer argument was null.
+ */
+#define DJADE_ERROR_NULL_POINTER 1
+
+/**
+ * The input was not valid UTF-8.
+ */
+#define DJADE_ERROR_INVALID_UTF8 2
+
+/**
+ * The options were invalid, such as an unsupported target version.
+ */
+#define DJADE_ERROR_INVALID_OPTIONS 3
+
+/**
+ * The output contained a NUL byte, so cannot be returned as a C string.
+ */
+#define DJADE_ERROR_NUL_BYTE 4
+
+/**
+ * Options for `djade_format()`.
+ */
+typedef struct DjadeOptions {
+  /**
+   * The Django version to target, or 0.0 for none.
+   */
+  uint8_t target_version_major;
+  uint8_t target_version_minor;
+  /**
+   * One of the `DJADE_NEWLINE_*` constants.
+   */
+  uint8_t newline;
+} DjadeOptions;
+
+
+
+/**
+ * Format the `len` bytes of UTF-8 template source at `input`.
+ *
+ * `options` may be null for the defaults. On success, returns `DJADE_OK`
+ * and stores a NUL-terminated string in `*out`, which must be released with
+ * `djade_free()`. On failure, returns a `DJADE_ERROR_*` code and leaves
+ * `*out` untouched.
+ *
+ * # Safety
+ *
+ * `input` must point to `len` readable bytes, `options` must be null or
+ * point to a valid `DjadeOptions`, and `out` must point to writable memory
+ * for a pointer.
+ */
+int32_t djade_format(const char *input, size_t len, const struct DjadeOptions *options, char **out);
+
+/**
+ * Release a string returned by `djade_format()`. Null is ignored.
+ *
+ * # Safety
+ *
+ * `s` must be null or a pointer from `djade_format()` that has not already
+ * been freed.
+ */
+void djade_free(char *s);
+
+#endif  /* DJADE_H */
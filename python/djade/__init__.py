@@ -0,0 +1,5 @@
+from __future__ import annotations
+
+from djade._djade import check, format_string, format_strings
+
+__all__ = ["check", "format_string", "format_strings"]
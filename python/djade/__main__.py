@@ -0,0 +1,13 @@
+from __future__ import annotations
+
+import sys
+
+from djade._djade import main as _main
+
+
+def main() -> None:
+    sys.exit(_main())
+
+
+if __name__ == "__main__":
+    main()
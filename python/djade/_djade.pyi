@@ -0,0 +1,10 @@
+from __future__ import annotations
+
+import os
+
+def format_string(text: str, target_version: str | None = None) -> str: ...
+def format_strings(
+    texts: list[str], target_version: str | None = None
+) -> list[str]: ...
+def check(path: str | os.PathLike[str], target_version: str | None = None) -> bool: ...
+def main() -> int: ...
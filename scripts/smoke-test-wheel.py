@@ -0,0 +1,38 @@
+# Call each function of an installed djade wheel’s extension module.
+# Usage: python scripts/smoke-test-wheel.py
+from __future__ import annotations
+
+import tempfile
+from pathlib import Path
+
+import djade
+
+assert djade.format_string("{{engine}}") == "{{ engine }}\n"
+assert djade.format_string("{% if a|length_is:1 %}{% endif %}", "4.2") == (
+    "{% if a|length == 1 %}{% endif %}\n"
+)
+
+texts = [f"{{{{e{i}}}}}" for i in range(100)]
+assert djade.format_strings(texts) == [f"{{{{ e{i} }}}}\n" for i in range(100)]
+assert djade.format_strings([]) == []
+
+for args in [("{{ a }}", "9.9"), ("{{ a }}", "four")]:
+    try:
+        djade.format_string(*args)
+    except ValueError:
+        pass
+    else:
+        raise AssertionError(f"format_string{args!r} didn’t raise ValueError")
+
+with tempfile.TemporaryDirectory() as directory:
+    formatted = Path(directory, "formatted.html")
+    formatted.write_text("{{ engine }}\n")
+    assert djade.check(formatted)
+    unformatted = Path(directory, "unformatted.html")
+    unformatted.write_text("{{engine}}")
+    assert not djade.check(str(unformatted))
+    jinja = Path(directory, "engine.jinja")
+    jinja.write_text("{%- if f(a, b) %}x{% endif -%}\n")
+    assert djade.check(jinja)
+
+print("OK")
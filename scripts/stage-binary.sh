@@ -0,0 +1,20 @@
+#!/bin/sh
+# Build the native djade binary and stage it in the wheel’s scripts data
+# directory, so wheels ship it alongside the extension module.
+# Usage: scripts/stage-binary.sh [rust-target-triple]
+set -eu
+
+if [ -n "${1:-}" ]; then
+    cargo build --release --locked --bin djade --target "$1"
+    dir="target/$1/release"
+else
+    cargo build --release --locked --bin djade
+    dir="target/release"
+fi
+
+mkdir -p python/djade.data/scripts
+for binary in djade djade.exe; do
+    if [ -f "$dir/$binary" ]; then
+        cp "$dir/$binary" python/djade.data/scripts/
+    fi
+done
@@ -0,0 +1,216 @@
+// C ABI, built with the “capi” feature. The header is include/djade.h,
+// generated with cbindgen:
+//
+//   cbindgen --config cbindgen.toml --output include/djade.h
+
+use crate::{FormatOptions, NewlinePolicy, Version, format_str};
+use std::ffi::{CString, c_char};
+
+/// Options for `djade_format()`.
+#[repr(C)]
+pub struct DjadeOptions {
+    /// The Django version to target, or 0.0 for none.
+    pub target_version_major: u8,
+    pub target_version_minor: u8,
+    /// One of the `DJADE_NEWLINE_*` constants.
+    pub newline: u8,
+}
+
+/// Match the template’s first newline.
+pub const DJADE_NEWLINE_AUTO: u8 = 0;
+/// Use `\n` for all newlines.
+pub const DJADE_NEWLINE_LF: u8 = 1;
+/// Use `\r\n` for all newlines.
+pub const DJADE_NEWLINE_CRLF: u8 = 2;
+
+/// Formatting succeeded.
+pub const DJADE_OK: i32 = 0;
+/// A required pointer argument was null.
+pub const DJADE_ERROR_NULL_POINTER: i32 = 1;
+/// The input was not valid UTF-8.
+pub const DJADE_ERROR_INVALID_UTF8: i32 = 2;
+/// The options were invalid, such as an unsupported target version.
+pub const DJADE_ERROR_INVALID_OPTIONS: i32 = 3;
+/// The output contained a NUL byte, so cannot be returned as a C string.
+pub const DJADE_ERROR_NUL_BYTE: i32 = 4;
+
+fn format_options(options: Option<&DjadeOptions>) -> Option<FormatOptions> {
+    let Some(options) = options else {
+        return Some(FormatOptions::default());
+    };
+    let target_version = match (options.target_version_major, options.target_version_minor) {
+        (0, 0) => None,
+        (major, minor) => Some(Version::new(major, minor)),
+    };
+    let newline = match options.newline {
+        DJADE_NEWLINE_AUTO => NewlinePolicy::Auto,
+        DJADE_NEWLINE_LF => NewlinePolicy::Lf,
+        DJADE_NEWLINE_CRLF => NewlinePolicy::CrLf,
+        _ => return None,
+    };
+    Some(FormatOptions {
+        target_version,
+        newline,
+        ..FormatOptions::default()
+    })
+}
+
+/// Format the `len` bytes of UTF-8 template source at `input`.
+///
+/// `options` may be null for the defaults. On success, returns `DJADE_OK`
+/// and stores a NUL-terminated string in `*out`, which must be released with
+/// `djade_free()`. On failure, returns a `DJADE_ERROR_*` code and leaves
+/// `*out` untouched.
+///
+/// # Safety
+///
+/// `input` must point to `len` readable bytes, `options` must be null or
+/// point to a valid `DjadeOptions`, and `out` must point to writable memory
+/// for a pointer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn djade_format(
+    input: *const c_char,
+    len: usize,
+    options: *const DjadeOptions,
+    out: *mut *mut c_char,
+) -> i32 {
+    if input.is_null() || out.is_null() {
+        return DJADE_ERROR_NULL_POINTER;
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(input.cast::<u8>(), len) };
+    let Ok(content) = std::str::from_utf8(bytes) else {
+        return DJADE_ERROR_INVALID_UTF8;
+    };
+    let Some(options) = format_options(unsafe { options.as_ref() }) else {
+        return DJADE_ERROR_INVALID_OPTIONS;
+    };
+
+    let formatted = match format_str(content, &options) {
+        Ok(formatted) => formatted,
+        Err(_) => return DJADE_ERROR_INVALID_OPTIONS,
+    };
+    let Ok(formatted) = CString::new(formatted) else {
+        return DJADE_ERROR_NUL_BYTE;
+    };
+    unsafe { *out = formatted.into_raw() };
+    DJADE_OK
+}
+
+/// Release a string returned by `djade_format()`. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a pointer from `djade_format()` that has not already
+/// been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn djade_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::ptr;
+
+    fn call_format(input: &[u8], options: Option<&DjadeOptions>) -> (i32, Option<String>) {
+        let mut out: *mut c_char = ptr::null_mut();
+        let options_ptr = options.map_or(ptr::null(), |o| o as *const DjadeOptions);
+        let status =
+            unsafe { djade_format(input.as_ptr().cast(), input.len(), options_ptr, &mut out) };
+        if out.is_null() {
+            return (status, None);
+        }
+        let result = unsafe { CStr::from_ptr(out) }.to_str().unwrap().to_string();
+        unsafe { djade_free(out) };
+        (status, Some(result))
+    }
+
+    #[test]
+    fn test_djade_format_default_options() {
+        assert_eq!(
+            call_format(b"{{engine}}", None),
+            (DJADE_OK, Some("{{ engine }}\n".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_djade_format_options() {
+        let options = DjadeOptions {
+            target_version_major: 3,
+            target_version_minor: 1,
+            newline: DJADE_NEWLINE_CRLF,
+        };
+        assert_eq!(
+            call_format(b"{% trans 'Hi' %}", Some(&options)),
+            (DJADE_OK, Some("{% translate 'Hi' %}\r\n".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_djade_format_unsupported_target_version() {
+        let options = DjadeOptions {
+            target_version_major: 4,
+            target_version_minor: 0,
+            newline: DJADE_NEWLINE_AUTO,
+        };
+        assert_eq!(
+            call_format(b"{{ engine }}", Some(&options)),
+            (DJADE_ERROR_INVALID_OPTIONS, None)
+        );
+    }
+
+    #[test]
+    fn test_djade_format_invalid_newline() {
+        let options = DjadeOptions {
+            target_version_major: 0,
+            target_version_minor: 0,
+            newline: 9,
+        };
+        assert_eq!(
+            call_format(b"{{ engine }}", Some(&options)),
+            (DJADE_ERROR_INVALID_OPTIONS, None)
+        );
+    }
+
+    #[test]
+    fn test_djade_format_empty_tag() {
+        assert_eq!(
+            call_format(b"{% %}{{a}}", None),
+            (DJADE_OK, Some("{% %}{{ a }}\n".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_djade_format_invalid_utf8() {
+        assert_eq!(
+            call_format(&[0xFF, 0xFE], None),
+            (DJADE_ERROR_INVALID_UTF8, None)
+        );
+    }
+
+    #[test]
+    fn test_djade_format_nul_byte() {
+        assert_eq!(
+            call_format(b"{{ a }}\0", None),
+            (DJADE_ERROR_NUL_BYTE, None)
+        );
+    }
+
+    #[test]
+    fn test_djade_format_null_pointers() {
+        let mut out: *mut c_char = ptr::null_mut();
+        let status = unsafe { djade_format(ptr::null(), 0, ptr::null(), &mut out) };
+        assert_eq!(status, DJADE_ERROR_NULL_POINTER);
+
+        let status = unsafe { djade_format(c"".as_ptr(), 0, ptr::null(), ptr::null_mut()) };
+        assert_eq!(status, DJADE_ERROR_NULL_POINTER);
+    }
+
+    #[test]
+    fn test_djade_free_null() {
+        unsafe { djade_free(ptr::null_mut()) };
+    }
+}
@@ -1,6 +1,17 @@
-use clap::Parser;
+use crate::tree::{Position, Tree};
+use crate::{
+    Dialect, DjadeError, Fixer, FormatOptions, QuoteStyle, SUPPORTED_TARGET_VERSIONS, Token,
+    Version, diagnose_str, format_str, jinja, lex,
+};
+use clap::builder::PossibleValuesParser;
+use clap::{Parser, Subcommand};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 
 #[derive(Parser, Debug)]
@@ -10,16 +21,20 @@ use std::sync::LazyLock;
     about = "A Django template formatter.",
     version
 )]
+#[command(subcommand_negates_reqs = true)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[arg(required = true, help = "Filenames to format, or '-' for stdin.")]
     pub filenames: Vec<String>,
 
     #[arg(
         long,
+        global = true,
         default_value = "auto",
-        // Versions also need adding below
-        value_parser = ["auto", "2.1", "2.2", "3.0", "3.1", "3.2", "4.1", "4.2", "5.0", "5.1", "5.2", "6.0", "6.1"],
-        help = "The version of Django to target.",
+        value_parser = target_version_parser(),
+        help = "The version of Django to target. 'auto' detects the minimum version from declared dependencies, 'locked' the version from lock files or a local virtualenv.",
     )]
     pub target_version: String,
 
@@ -28,24 +43,513 @@ pub struct Args {
         help = "Avoid writing any formatted files back. Instead, exit with a non-zero status code if any files would have been modified, and zero otherwise."
     )]
     pub check: bool,
+
+    #[arg(
+        long = "enable-fixer",
+        global = true,
+        value_name = "FIXER",
+        value_parser = opt_in_fixer_parser(),
+        help = "Enable an opt-in fixer. May be given multiple times."
+    )]
+    pub enable_fixers: Vec<String>,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = "auto",
+        value_parser = ["auto", "django", "jinja2"],
+        help = "The template language. 'auto' picks Jinja2 for .jinja, .jinja2, and .j2 files, and Django otherwise."
+    )]
+    pub dialect: String,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Version {
-    pub major: u8,
-    pub minor: u8,
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    #[command(
+        about = "Show the target version, fixers, and formatters that apply to a template, and where the target version came from."
+    )]
+    ShowConfig {
+        #[arg(help = "Template filename.")]
+        path: String,
+    },
+    #[command(about = "Show the tokens that Djade lexes a template into.")]
+    Parse {
+        #[arg(help = "Template filename, or - for standard input.")]
+        path: String,
+        #[arg(
+            long,
+            help = "Output the tokens as JSON, rather than one line per token."
+        )]
+        json: bool,
+    },
+}
+
+/// Run the command line interface with the given arguments, returning the
+/// exit code.
+pub fn run<I, T>(args: I) -> i32
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = match Args::try_parse_from(args) {
+        Ok(args) => args,
+        Err(e) => {
+            e.print().unwrap();
+            return e.exit_code();
+        }
+    };
+    match &args.command {
+        Some(Command::ShowConfig { path }) => {
+            show_config(&args, path, &mut std::io::stdout(), &mut std::io::stderr())
+        }
+        Some(Command::Parse { path, json }) => {
+            let dialect = resolve_dialect(&args.dialect, path);
+            parse(
+                path,
+                dialect,
+                *json,
+                &mut std::io::stdout(),
+                &mut std::io::stderr(),
+            )
+        }
+        None => main_impl(&args, &mut std::io::stderr()),
+    }
+}
+
+fn main_impl(args: &Args, writer: &mut dyn std::io::Write) -> i32 {
+    let config = match load_config("pyproject.toml") {
+        Ok(config) => config,
+        Err(e) => {
+            writeln!(writer, "Error: {}", e).unwrap();
+            return 1;
+        }
+    };
+    let mut options = FormatOptions {
+        target_version: get_target_version(&args.target_version),
+        fixers: selected_fixers(args),
+        paired_tags: config.paired_tags,
+        quote_style: config.quote_style,
+        ..FormatOptions::default()
+    };
+
+    let mut returncode = 0;
+    let mut reformatted_count = 0;
+    let mut already_formatted_count = 0;
+    for filename in &args.filenames {
+        let is_stdin = filename == "-";
+        options.dialect = resolve_dialect(&args.dialect, filename);
+        let content = match read_template(filename) {
+            Ok(content) => content,
+            Err(e) => {
+                writeln!(writer, "{}", e).unwrap();
+                returncode = 1;
+                continue;
+            }
+        };
+
+        let formatted = match format_str(&content, &options) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                writeln!(writer, "Error formatting {}: {}", filename, e).unwrap();
+                returncode = 1;
+                continue;
+            }
+        };
+
+        // Diagnose the template as it will be left, so locations match and
+        // problems fixed by formatting aren’t reported. Diagnostics follow
+        // Django’s parsing rules, so don’t apply to Jinja2 templates.
+        let display_name = if is_stdin { "stdin" } else { filename };
+        let result = if args.check { &content } else { &formatted };
+        let diagnostics = match options.dialect {
+            Dialect::Django => diagnose_str(result, &options).unwrap_or_default(),
+            Dialect::Jinja2 => Vec::new(),
+        };
+        for diagnostic in diagnostics {
+            if args.check {
+                writeln!(writer, "Error: {}: {}", display_name, diagnostic).unwrap();
+                returncode = 1;
+            } else {
+                writeln!(writer, "Warning: {}: {}", display_name, diagnostic).unwrap();
+            }
+        }
+
+        if formatted != content {
+            if args.check {
+                writeln!(writer, "Would reformat: {}", display_name).unwrap();
+                returncode = 1;
+                reformatted_count += 1;
+            } else if is_stdin {
+                print!("{}", formatted);
+                reformatted_count += 1;
+            } else {
+                fs::write(filename, formatted).expect("Could not write {filename}");
+                returncode = 1;
+                reformatted_count += 1;
+            }
+        } else {
+            if is_stdin && !args.check {
+                print!("{}", content);
+            }
+            already_formatted_count += 1;
+        }
+    }
+
+    let mut message = String::new();
+    if reformatted_count > 0 {
+        message.push_str(&reformatted_count.to_string());
+        message.push_str(" file");
+        if reformatted_count > 1 {
+            message.push('s');
+        }
+        if args.check {
+            message.push_str(" would be reformatted");
+        } else {
+            message.push_str(" reformatted");
+        }
+        if already_formatted_count > 0 {
+            message.push_str(", ");
+        }
+    }
+    if already_formatted_count > 0 {
+        message.push_str(&already_formatted_count.to_string());
+        message.push_str(" file");
+        if already_formatted_count > 1 {
+            message.push('s');
+        }
+        message.push_str(" already formatted");
+    }
+    if !message.is_empty() {
+        writeln!(writer, "{}", message).unwrap();
+    }
+
+    returncode
+}
+
+fn parse(
+    path: &str,
+    dialect: Dialect,
+    json: bool,
+    writer: &mut dyn std::io::Write,
+    error_writer: &mut dyn std::io::Write,
+) -> i32 {
+    let content = match read_template(path) {
+        Ok(content) => content,
+        Err(e) => {
+            writeln!(error_writer, "{}", e).unwrap();
+            return 1;
+        }
+    };
+
+    let tokens = match dialect {
+        Dialect::Django => Ok(lex(&content)),
+        Dialect::Jinja2 => jinja::lex(&content),
+    };
+    let tokens = match tokens {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            writeln!(error_writer, "Error parsing {}: {}", path, e).unwrap();
+            return 1;
+        }
+    };
+
+    let tree = match dialect {
+        Dialect::Django => Tree::parse(&tokens),
+        Dialect::Jinja2 => Tree::parse_jinja2(&tokens, &[]),
+    };
+    let parsed: Vec<_> = tokens
+        .iter()
+        .zip(tree.positions)
+        .map(|(token, position)| ParsedToken { token, position })
+        .collect();
+    let written = if json {
+        write_json(&parsed, writer)
+    } else {
+        write_token_lines(&content, &parsed, writer)
+    };
+    match written {
+        Ok(()) => 0,
+        // The reader closed the pipe, like `head` does, so has all it wants.
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => 0,
+        Err(e) => {
+            writeln!(error_writer, "Error writing tokens: {}", e).unwrap();
+            1
+        }
+    }
+}
+
+fn write_json(parsed: &[ParsedToken], writer: &mut dyn std::io::Write) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *writer, parsed)?;
+    writeln!(writer)
+}
+
+/// Write each token on a line with its location, its type indented by its
+/// depth, and its source.
+fn write_token_lines(
+    content: &str,
+    parsed: &[ParsedToken],
+    writer: &mut dyn std::io::Write,
+) -> io::Result<()> {
+    for ParsedToken { token, position } in parsed {
+        let span = token.span();
+        writeln!(
+            writer,
+            "{}:{} {}{} {:?}",
+            span.location.line,
+            span.location.column,
+            "  ".repeat(position.depth),
+            token.kind(),
+            &content[span.start..span.end]
+        )?;
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct ParsedToken<'t, 'a> {
+    #[serde(flatten)]
+    token: &'t Token<'a>,
+    #[serde(flatten)]
+    position: Position,
+}
+
+/// The dialect of a template, from `--dialect` or its extension. Standard
+/// input is Django unless `--dialect` says otherwise.
+fn resolve_dialect(dialect: &str, filename: &str) -> Dialect {
+    match dialect {
+        "django" => Dialect::Django,
+        "jinja2" => Dialect::Jinja2,
+        _ => Dialect::from_path(Path::new(filename)),
+    }
+}
+
+/// Read a template, with `-` meaning standard input.
+fn read_template(filename: &str) -> Result<String, DjadeError> {
+    if filename == "-" {
+        let mut buffer = String::new();
+        io::stdin()
+            .read_to_string(&mut buffer)
+            .map_err(|e| DjadeError::from_read(None, e))?;
+        Ok(buffer)
+    } else {
+        fs::read_to_string(filename)
+            .map_err(|e| DjadeError::from_read(Some(Path::new(filename)), e))
+    }
+}
+
+/// Settings from the `[tool.djade]` table of pyproject.toml.
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct Config {
+    paired_tags: Vec<String>,
+    quote_style: Option<QuoteStyle>,
+}
+
+/// Load settings from a pyproject.toml, using the defaults if it’s missing
+/// or has no `[tool.djade]` table.
+fn load_config(path: &str) -> Result<Config, DjadeError> {
+    let Some(table) = fs::read_to_string(path)
+        .ok()
+        .and_then(|content| toml::from_str::<toml::Value>(&content).ok())
+        .and_then(|pyproject| pyproject.get("tool")?.get("djade").cloned())
+    else {
+        return Ok(Config::default());
+    };
+    let config: Config = table
+        .try_into()
+        .map_err(|e: toml::de::Error| DjadeError::Config {
+            message: format!("tool.djade in {}: {}", path, e.message()),
+        })?;
+    if let Some(tag) = config
+        .paired_tags
+        .iter()
+        .find(|tag| tag.is_empty() || tag.contains(char::is_whitespace))
+    {
+        return Err(DjadeError::Config {
+            message: format!(
+                "tool.djade.paired-tags in {}: invalid tag name {:?}",
+                path, tag
+            ),
+        });
+    }
+    Ok(config)
 }
 
-impl Version {
-    pub fn new(major: u8, minor: u8) -> Self {
-        Self { major, minor }
+fn show_config(
+    args: &Args,
+    path: &str,
+    writer: &mut dyn std::io::Write,
+    error_writer: &mut dyn std::io::Write,
+) -> i32 {
+    if let Err(e) = fs::metadata(path) {
+        writeln!(
+            error_writer,
+            "{}",
+            DjadeError::from_read(Some(Path::new(path)), e)
+        )
+        .unwrap();
+        return 1;
+    }
+
+    let config = match load_config("pyproject.toml") {
+        Ok(config) => config,
+        Err(e) => {
+            writeln!(error_writer, "Error: {}", e).unwrap();
+            return 1;
+        }
+    };
+
+    let resolved = resolve_target_version(&args.target_version);
+    let target_version = resolved.as_ref().map(|r| r.version);
+
+    let dialect = resolve_dialect(&args.dialect, path);
+    let dialect_source = match (args.dialect.as_str(), dialect) {
+        ("auto", Dialect::Django) => "default",
+        ("auto", Dialect::Jinja2) => "from file extension",
+        _ => "from --dialect",
+    };
+
+    writeln!(writer, "Template: {}", path).unwrap();
+    writeln!(writer, "Dialect: {} ({})", dialect.name(), dialect_source).unwrap();
+    match &resolved {
+        Some(resolved) => {
+            write!(
+                writer,
+                "Target version: {} (from {}",
+                resolved.version, resolved.source
+            )
+            .unwrap();
+            if let Some(detected) = resolved.detected
+                && detected != resolved.version
+            {
+                write!(writer, ", detected {}", detected).unwrap();
+            }
+            writeln!(writer, ")").unwrap();
+        }
+        None => writeln!(
+            writer,
+            "Target version: none (not detected from --target-version {})",
+            args.target_version
+        )
+        .unwrap(),
+    }
+
+    writeln!(writer, "Fixers:").unwrap();
+    let fixers = selected_fixers(args);
+    for fixer in Fixer::ALL {
+        let requirement = match fixer.min_version() {
+            Some(version) => format!("Django {}+", version),
+            None if fixer.is_opt_in() => "opt-in".to_string(),
+            None => "always".to_string(),
+        };
+        let status = if dialect == Dialect::Django
+            && fixers.contains(&fixer)
+            && fixer.is_active(target_version)
+        {
+            "active"
+        } else {
+            "inactive"
+        };
+        writeln!(writer, "  {} ({}): {}", fixer.name(), requirement, status).unwrap();
+    }
+
+    writeln!(writer, "Formatters:").unwrap();
+    let options = FormatOptions {
+        quote_style: config.quote_style,
+        dialect,
+        ..FormatOptions::default()
+    };
+    for formatter in dialect.formatters() {
+        if formatter.is_enabled(&options) {
+            writeln!(writer, "  {}", formatter.name()).unwrap();
+        }
+    }
+
+    if !config.paired_tags.is_empty() {
+        writeln!(writer, "Paired tags (from pyproject.toml):").unwrap();
+        for tag in &config.paired_tags {
+            writeln!(writer, "  {} ... end{}", tag, tag).unwrap();
+        }
+    }
+
+    if let Some(quote_style) = config.quote_style
+        && dialect == Dialect::Django
+    {
+        writeln!(
+            writer,
+            "Quote style (from pyproject.toml): {}",
+            quote_style.name()
+        )
+        .unwrap();
+    }
+
+    writeln!(writer, "Exclusions:").unwrap();
+    for exclusion in dialect.exclusions() {
+        writeln!(writer, "  {}", exclusion).unwrap();
     }
 
-    pub fn as_tuple(&self) -> (u8, u8) {
-        (self.major, self.minor)
+    0
+}
+
+fn opt_in_fixer_parser() -> PossibleValuesParser {
+    PossibleValuesParser::new(
+        Fixer::ALL
+            .into_iter()
+            .filter(|fixer| fixer.is_opt_in())
+            .map(Fixer::name),
+    )
+}
+
+/// The default fixers, plus the opt-in ones enabled on the command line.
+fn selected_fixers(args: &Args) -> Vec<Fixer> {
+    Fixer::ALL
+        .into_iter()
+        .filter(|fixer| {
+            !fixer.is_opt_in() || args.enable_fixers.iter().any(|name| name == fixer.name())
+        })
+        .collect()
+}
+
+fn target_version_parser() -> PossibleValuesParser {
+    PossibleValuesParser::new(
+        ["auto", "locked"]
+            .iter()
+            .chain(SUPPORTED_TARGET_VERSIONS)
+            .copied(),
+    )
+}
+
+fn supported_versions() -> impl DoubleEndedIterator<Item = Version> {
+    SUPPORTED_TARGET_VERSIONS
+        .iter()
+        .map(|version_str| parse_version_string(version_str).unwrap())
+}
+
+/// A Django version found in a project file, and a description of where.
+#[derive(Debug, PartialEq)]
+pub struct DetectedVersion {
+    pub version: Version,
+    pub source: String,
+}
+
+impl DetectedVersion {
+    fn new(version: Version, source: impl Into<String>) -> Self {
+        Self {
+            version,
+            source: source.into(),
+        }
     }
 }
 
+/// The version to target, where it came from, and, if detected, the
+/// version found before mapping it onto a supported one.
+#[derive(Debug, PartialEq)]
+pub struct ResolvedVersion {
+    pub version: Version,
+    pub source: String,
+    pub detected: Option<Version>,
+}
+
 static DJANGO_VERSION_RE: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(
         r"(?xi)
@@ -89,60 +593,528 @@ static DJANGO_VERSION_RE: LazyLock<Regex> = LazyLock::new(|| {
 });
 
 pub fn get_target_version(version_str: &str) -> Option<Version> {
-    if version_str != "auto" {
-        return parse_version_string(version_str);
+    let resolved = resolve_target_version(version_str)?;
+    if let Some(detected) = resolved.detected {
+        eprintln!(
+            "Detected Django version from {}: {}",
+            resolved.source, detected
+        );
+        if detected > resolved.version {
+            eprintln!(
+                "Warning: Django {} is newer than the latest version Djade supports, targeting {} instead.",
+                detected, resolved.version
+            );
+        }
     }
+    Some(resolved.version)
+}
 
+/// Resolve a `--target-version` value without reporting anything.
+pub fn resolve_target_version(version_str: &str) -> Option<ResolvedVersion> {
+    let detected = match version_str {
+        "auto" => detect_declared_version(),
+        "locked" => detect_version_from_lock_files(Path::new("."))
+            .or_else(|| detect_version_from_virtualenv(Path::new(".")))
+            .or_else(detect_declared_version),
+        _ => {
+            return parse_version_string(version_str).map(|version| ResolvedVersion {
+                version,
+                source: "--target-version".to_string(),
+                detected: None,
+            });
+        }
+    }?;
+
+    Some(ResolvedVersion {
+        version: resolve_detected_version(detected.version)?,
+        source: detected.source,
+        detected: Some(detected.version),
+    })
+}
+
+fn detect_declared_version() -> Option<DetectedVersion> {
     detect_version_from_pyproject_toml("pyproject.toml")
+        .or_else(|| detect_version_from_setup_cfg("setup.cfg"))
+        .or_else(|| detect_version_from_setup_py("setup.py"))
+        .or_else(|| detect_version_from_requirements_files(Path::new(".")))
+}
+
+/// Map a detected version onto the newest supported version at or below it.
+/// Versions newer than Djade knows about are clamped, so that all fixers
+/// still apply.
+fn resolve_detected_version(version: Version) -> Option<Version> {
+    let newest = supported_versions().next_back().unwrap();
+    if version > newest {
+        return Some(newest);
+    }
+    supported_versions()
+        .rev()
+        .find(|supported| *supported <= version)
 }
 
 fn parse_version_string(version_str: &str) -> Option<Version> {
-    let parts: Vec<&str> = version_str.split('.').collect();
-    if parts.len() != 2 {
-        return None;
+    version_str.parse().ok()
+}
+
+fn detect_version_from_pyproject_toml(path: &str) -> Option<DetectedVersion> {
+    let content = fs::read_to_string(path).ok()?;
+    let config: toml::Value = toml::from_str(&content).ok()?;
+
+    let (version, table) = find_django_version_in_pyproject(&config)?;
+    Some(DetectedVersion::new(
+        version,
+        format!("pyproject.toml ({})", table),
+    ))
+}
+
+/// Search the dependency tables of a parsed pyproject.toml, returning the
+/// first Django version found and the name of the table it came from.
+fn find_django_version_in_pyproject(config: &toml::Value) -> Option<(Version, String)> {
+    let project = config.get("project");
+    let poetry = config.get("tool").and_then(|tool| tool.get("poetry"));
+    let pdm = config.get("tool").and_then(|tool| tool.get("pdm"));
+
+    // Main dependencies
+    if let Some(version) = project
+        .and_then(|project| project.get("dependencies"))
+        .and_then(pep508_array_version)
+    {
+        return Some((version, "project.dependencies".to_string()));
+    }
+    if let Some(version) = poetry
+        .and_then(|poetry| poetry.get("dependencies"))
+        .and_then(poetry_table_version)
+    {
+        return Some((version, "tool.poetry.dependencies".to_string()));
     }
 
-    let major = parts[0].parse::<u8>().ok()?;
-    let minor = parts[1].parse::<u8>().ok()?;
+    // Optional and development dependencies
+    let pep508_groups = [
+        (
+            "project.optional-dependencies",
+            project.and_then(|p| p.get("optional-dependencies")),
+        ),
+        ("dependency-groups", config.get("dependency-groups")),
+        (
+            "tool.pdm.dev-dependencies",
+            pdm.and_then(|pdm| pdm.get("dev-dependencies")),
+        ),
+    ];
+    for (table_name, table) in pep508_groups {
+        for (group, dependencies) in table.and_then(toml::Value::as_table).into_iter().flatten() {
+            if let Some(version) = pep508_array_version(dependencies) {
+                return Some((version, format!("{}.{}", table_name, group)));
+            }
+        }
+    }
 
-    Some(Version::new(major, minor))
+    for (group, group_table) in poetry
+        .and_then(|poetry| poetry.get("group"))
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flatten()
+    {
+        if let Some(version) = group_table
+            .get("dependencies")
+            .and_then(poetry_table_version)
+        {
+            return Some((version, format!("tool.poetry.group.{}.dependencies", group)));
+        }
+    }
+    if let Some(version) = poetry
+        .and_then(|poetry| poetry.get("dev-dependencies"))
+        .and_then(poetry_table_version)
+    {
+        return Some((version, "tool.poetry.dev-dependencies".to_string()));
+    }
+
+    None
+}
+
+fn pep508_array_version(dependencies: &toml::Value) -> Option<Version> {
+    find_django_version(
+        dependencies
+            .as_array()?
+            .iter()
+            .filter_map(toml::Value::as_str),
+    )
+}
+
+fn poetry_table_version(dependencies: &toml::Value) -> Option<Version> {
+    let (_, spec) = dependencies
+        .as_table()?
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("django"))?;
+
+    // Multiple constraints are given as an array of tables, e.g. per Python
+    // version, so take the lowest.
+    let constraints: Vec<&toml::Value> = match spec {
+        toml::Value::Array(items) => items.iter().collect(),
+        _ => vec![spec],
+    };
+    constraints
+        .into_iter()
+        .filter_map(|constraint| match constraint {
+            toml::Value::String(s) => Some(s.as_str()),
+            toml::Value::Table(t) => t.get("version").and_then(toml::Value::as_str),
+            _ => None,
+        })
+        .filter_map(parse_poetry_constraint)
+        .min()
+}
+
+static POETRY_CONSTRAINT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?x)
+        ^\s*
+        (?:\^|~=|~|>=|==|=)?
+        \s*
+        (?P<major>[0-9]+)
+        (?:
+            \.
+            (?P<minor>[0-9]+)
+        )?
+        ",
+    )
+    .unwrap()
+});
+
+/// Parse the minimum version from a Poetry constraint such as `^4.2`,
+/// `~4.2.1`, `4.2.*`, or `>=4.2,<5.0`. Alternatives separated by `||`
+/// give their lowest version.
+fn parse_poetry_constraint(constraint: &str) -> Option<Version> {
+    constraint
+        .split("||")
+        .filter_map(|alternative| {
+            let captures = POETRY_CONSTRAINT_RE.captures(alternative)?;
+            let major = captures.name("major")?.as_str().parse::<u8>().ok()?;
+            let minor = match captures.name("minor") {
+                Some(minor_match) => minor_match.as_str().parse::<u8>().ok()?,
+                None => 0,
+            };
+            Some(Version::new(major, minor))
+        })
+        .min()
 }
 
-const SUPPORTED_TARGET_VERSIONS: &[(u8, u8)] = &[
-    (2, 1),
-    (2, 2),
-    (3, 0),
-    (3, 1),
-    (3, 2),
-    (4, 1),
-    (4, 2),
-    (5, 0),
-    (5, 1),
-    (5, 2),
-];
-
-fn detect_version_from_pyproject_toml(path: &str) -> Option<Version> {
+fn detect_version_from_setup_cfg(path: &str) -> Option<DetectedVersion> {
     let content = fs::read_to_string(path).ok()?;
-    let config: toml::Value = toml::from_str(&content).ok()?;
+    let dependencies = setup_cfg_install_requires(&content)?;
+
+    let version = find_django_version(dependencies.iter().map(String::as_str))?;
+    Some(DetectedVersion::new(version, "setup.cfg"))
+}
 
-    let dependencies = config.get("project")?.get("dependencies")?.as_array()?;
+/// Extract the `install_requires` value from the `[options]` section of a
+/// setup.cfg file, one requirement per line.
+fn setup_cfg_install_requires(content: &str) -> Option<Vec<String>> {
+    let mut in_options = false;
+    let mut in_install_requires = false;
+    let mut dependencies = Vec::new();
+    let mut found = false;
 
-    for dep in dependencies {
-        if let Some(dep_str) = dep.as_str()
-            && let Some(version) = parse_django_dependency(dep_str)
-            && SUPPORTED_TARGET_VERSIONS.contains(&version.as_tuple())
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_options = trimmed == "[options]";
+            in_install_requires = false;
+            continue;
+        }
+        if !in_options {
+            continue;
+        }
+        if trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+
+        // Like configparser, blank lines don’t end a multi-line value.
+        if in_install_requires && (trimmed.is_empty() || line.starts_with(char::is_whitespace)) {
+            if !trimmed.is_empty() {
+                dependencies.push(trimmed.to_string());
+            }
+            continue;
+        }
+        in_install_requires = false;
+
+        if let Some((key, value)) = trimmed.split_once(['=', ':'])
+            && key.trim() == "install_requires"
         {
-            eprintln!(
-                "Detected Django version from pyproject.toml: {}.{}",
-                version.major, version.minor
-            );
-            return Some(version);
+            found = true;
+            in_install_requires = true;
+            let value = value.trim();
+            if !value.is_empty() {
+                dependencies.push(value.to_string());
+            }
+        }
+    }
+
+    found.then_some(dependencies)
+}
+
+static SETUP_PY_INSTALL_REQUIRES_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\binstall_requires\s*=\s*[\[(]").unwrap());
+
+fn detect_version_from_setup_py(path: &str) -> Option<DetectedVersion> {
+    let content = fs::read_to_string(path).ok()?;
+    let dependencies = setup_py_install_requires(&content)?;
+
+    let version = find_django_version(dependencies.iter().map(String::as_str))?;
+    Some(DetectedVersion::new(version, "setup.py"))
+}
+
+/// Extract the string literals from a static `install_requires=[...]` list
+/// in a setup.py file.
+fn setup_py_install_requires(content: &str) -> Option<Vec<String>> {
+    let start = SETUP_PY_INSTALL_REQUIRES_RE.find(content)?.end();
+    let mut dependencies = Vec::new();
+    let mut chars = content[start..].chars();
+    let mut in_comment = false;
+
+    while let Some(c) = chars.next() {
+        if in_comment {
+            in_comment = c != '\n';
+            continue;
+        }
+        match c {
+            ']' | ')' => return Some(dependencies),
+            '#' => in_comment = true,
+            '"' | '\'' => {
+                let mut literal = String::new();
+                loop {
+                    match chars.next()? {
+                        '\\' => literal.push(chars.next()?),
+                        q if q == c => break,
+                        other => literal.push(other),
+                    }
+                }
+                dependencies.push(literal);
+            }
+            // Anything other than literals means the list isn't static.
+            c if c.is_whitespace() || c == ',' => {}
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+fn find_django_version<'a>(dependencies: impl IntoIterator<Item = &'a str>) -> Option<Version> {
+    dependencies.into_iter().find_map(parse_django_dependency)
+}
+
+const REQUIREMENTS_EXTENSIONS: &[&str] = &["txt", "in", "lock"];
+
+fn detect_version_from_requirements_files(dir: &Path) -> Option<DetectedVersion> {
+    let mut visited = HashSet::new();
+    for path in find_requirements_files(dir) {
+        if let Some((version, found_in)) =
+            detect_version_from_requirements_file(&path, &mut visited)
+        {
+            let display_path = found_in.strip_prefix(dir).unwrap_or(&found_in);
+            return Some(DetectedVersion::new(
+                version,
+                display_path.display().to_string(),
+            ));
+        }
+    }
+
+    None
+}
+
+fn find_requirements_files(dir: &Path) -> Vec<PathBuf> {
+    let is_requirements_file = |path: &Path| {
+        path.is_file()
+            && path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| REQUIREMENTS_EXTENSIONS.contains(&ext))
+    };
+    let sorted_files = |dir: &Path, prefix: &str| {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(prefix))
+                    && is_requirements_file(path)
+            })
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    // requirements.txt first, then other top-level files, then those in a
+    // requirements/ directory.
+    let mut paths = Vec::new();
+    let main_file = dir.join("requirements.txt");
+    if main_file.is_file() {
+        paths.push(main_file.clone());
+    }
+    paths.extend(
+        sorted_files(dir, "requirements")
+            .into_iter()
+            .filter(|path| *path != main_file),
+    );
+    paths.extend(sorted_files(&dir.join("requirements"), ""));
+    paths
+}
+
+fn detect_version_from_requirements_file(
+    path: &Path,
+    visited: &mut HashSet<PathBuf>,
+) -> Option<(Version, PathBuf)> {
+    let canonical = path.canonicalize().ok()?;
+    if !visited.insert(canonical) {
+        return None;
+    }
+    let content = fs::read_to_string(path).ok()?;
+
+    for line in requirements_lines(&content) {
+        let include = line
+            .strip_prefix("-r")
+            .or_else(|| line.strip_prefix("--requirement"));
+        if let Some(include) = include {
+            let include = include.trim_start_matches('=').trim();
+            let include_path = path.parent().unwrap_or(Path::new(".")).join(include);
+            if let Some(found) = detect_version_from_requirements_file(&include_path, visited) {
+                return Some(found);
+            }
+        } else if let Some(version) = parse_django_dependency(&line) {
+            return Some((version, path.to_path_buf()));
+        }
+    }
+
+    None
+}
+
+/// Logical lines of a pip requirements file, with comments removed and
+/// backslash continuations joined.
+fn requirements_lines(content: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for raw_line in content.lines() {
+        let line = match raw_line.find('#') {
+            Some(0) => "",
+            Some(idx) if raw_line[..idx].ends_with(char::is_whitespace) => &raw_line[..idx],
+            _ => raw_line,
+        };
+        if let Some(continued) = line.trim_end().strip_suffix('\\') {
+            current.push_str(continued);
+            current.push(' ');
+            continue;
+        }
+        current.push_str(line);
+        let logical = current.trim();
+        if !logical.is_empty() {
+            lines.push(logical.to_string());
+        }
+        current.clear();
+    }
+    let logical = current.trim();
+    if !logical.is_empty() {
+        lines.push(logical.to_string());
+    }
+    lines
+}
+
+const LOCK_FILES: &[&str] = &["uv.lock", "poetry.lock", "pdm.lock"];
+
+fn detect_version_from_lock_files(dir: &Path) -> Option<DetectedVersion> {
+    for lock_file in LOCK_FILES {
+        if let Ok(content) = fs::read_to_string(dir.join(lock_file))
+            && let Some(version) = parse_lock_file(&content)
+        {
+            return Some(DetectedVersion::new(version, *lock_file));
+        }
+    }
+
+    None
+}
+
+/// Find the locked Django version in a uv, Poetry, or PDM lock file, which
+/// all list packages as `[[package]]` tables with `name` and `version` keys.
+fn parse_lock_file(content: &str) -> Option<Version> {
+    let lock: toml::Value = toml::from_str(content).ok()?;
+    lock.get("package")?
+        .as_array()?
+        .iter()
+        .find(|package| {
+            package
+                .get("name")
+                .and_then(toml::Value::as_str)
+                .is_some_and(|name| name.eq_ignore_ascii_case("django"))
+        })?
+        .get("version")?
+        .as_str()
+        .and_then(parse_release_version)
+}
+
+static RELEASE_VERSION_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?P<major>[0-9]+)\.(?P<minor>[0-9]+)").unwrap());
+
+fn parse_release_version(version_str: &str) -> Option<Version> {
+    let captures = RELEASE_VERSION_RE.captures(version_str)?;
+    let major = captures.name("major")?.as_str().parse::<u8>().ok()?;
+    let minor = captures.name("minor")?.as_str().parse::<u8>().ok()?;
+    Some(Version::new(major, minor))
+}
+
+fn detect_version_from_virtualenv(dir: &Path) -> Option<DetectedVersion> {
+    let mut venvs: Vec<PathBuf> = Vec::new();
+    if let Some(virtual_env) = std::env::var_os("VIRTUAL_ENV") {
+        venvs.push(PathBuf::from(virtual_env));
+    }
+    venvs.push(dir.join(".venv"));
+    venvs.push(dir.join("venv"));
+
+    for venv in venvs {
+        if let Some(version) = find_django_in_virtualenv(&venv) {
+            let display_path = venv.strip_prefix(dir).unwrap_or(&venv);
+            return Some(DetectedVersion::new(
+                version,
+                format!("virtualenv {}", display_path.display()),
+            ));
         }
     }
 
     None
 }
 
+fn find_django_in_virtualenv(venv: &Path) -> Option<Version> {
+    // Windows layout, then POSIX layout with a versioned lib/pythonX.Y/
+    let mut site_packages_dirs = vec![venv.join("Lib").join("site-packages")];
+    let mut lib_dirs: Vec<PathBuf> = fs::read_dir(venv.join("lib"))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("python"))
+        })
+        .collect();
+    lib_dirs.sort();
+    site_packages_dirs.extend(lib_dirs.into_iter().map(|lib| lib.join("site-packages")));
+
+    site_packages_dirs.into_iter().find_map(|site_packages| {
+        let content = fs::read_to_string(site_packages.join("django").join("__init__.py")).ok()?;
+        parse_django_init_version(&content)
+    })
+}
+
+static DJANGO_INIT_VERSION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^VERSION\s*=\s*\(\s*(?P<major>[0-9]+)\s*,\s*(?P<minor>[0-9]+)").unwrap()
+});
+
+fn parse_django_init_version(content: &str) -> Option<Version> {
+    let captures = DJANGO_INIT_VERSION_RE.captures(content)?;
+    let major = captures.name("major")?.as_str().parse::<u8>().ok()?;
+    let minor = captures.name("minor")?.as_str().parse::<u8>().ok()?;
+    Some(Version::new(major, minor))
+}
+
 fn parse_django_dependency(dep_str: &str) -> Option<Version> {
     let lowercase_dep = dep_str.to_lowercase();
     let captures = DJANGO_VERSION_RE.captures(&lowercase_dep)?;
@@ -162,6 +1134,8 @@ mod tests {
     use super::*;
     use clap::CommandFactory;
     use std::fs;
+    use std::fs::File;
+    use std::io::Write;
     use tempfile::tempdir;
 
     #[test]
@@ -257,12 +1231,82 @@ mod tests {
         assert_eq!(parse_django_dependency("invalid"), None);
     }
 
-    #[test]
-    fn test_detect_version_from_pyproject_toml() {
+    fn config_from(content: &str) -> Result<Config, DjadeError> {
         let temp_dir = tempdir().unwrap();
         let pyproject_path = temp_dir.path().join("pyproject.toml");
+        fs::write(&pyproject_path, content).unwrap();
+        load_config(pyproject_path.to_str().unwrap())
+    }
 
-        // Test with Django dependency
+    #[test]
+    fn test_load_config_paired_tags() {
+        let config =
+            config_from("[tool.djade]\npaired-tags = [\"component\", \"slot\"]\n").unwrap();
+        assert_eq!(config.paired_tags, vec!["component", "slot"]);
+    }
+
+    #[test]
+    fn test_load_config_no_table() {
+        let config = config_from("[project]\nname = \"engine\"\n").unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_load_config_missing_file() {
+        assert_eq!(load_config("nonexistent.toml").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_load_config_unknown_key() {
+        let error = config_from("[tool.djade]\nwhistle = true\n").unwrap_err();
+        assert!(matches!(error, DjadeError::Config { .. }));
+        assert!(
+            error
+                .to_string()
+                .starts_with("invalid configuration: tool.djade in ")
+        );
+        assert!(error.to_string().contains("unknown field `whistle`"));
+    }
+
+    #[test]
+    fn test_load_config_paired_tags_wrong_type() {
+        let error = config_from("[tool.djade]\npaired-tags = \"slot\"\n").unwrap_err();
+        assert!(matches!(error, DjadeError::Config { .. }));
+    }
+
+    #[test]
+    fn test_load_config_paired_tags_invalid_name() {
+        let error = config_from("[tool.djade]\npaired-tags = [\"my slot\"]\n").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .ends_with(": invalid tag name \"my slot\"")
+        );
+    }
+
+    #[test]
+    fn test_load_config_quote_style() {
+        let config = config_from("[tool.djade]\nquote-style = \"single\"\n").unwrap();
+        assert_eq!(config.quote_style, Some(QuoteStyle::Single));
+    }
+
+    #[test]
+    fn test_load_config_quote_style_invalid() {
+        let error = config_from("[tool.djade]\nquote-style = \"curly\"\n").unwrap_err();
+        assert!(matches!(error, DjadeError::Config { .. }));
+        assert!(
+            error
+                .to_string()
+                .contains("unknown variant `curly`, expected `double` or `single`")
+        );
+    }
+
+    #[test]
+    fn test_detect_version_from_pyproject_toml() {
+        let temp_dir = tempdir().unwrap();
+        let pyproject_path = temp_dir.path().join("pyproject.toml");
+
+        // Test with Django dependency
         let pyproject_content = r#"
 [project]
 dependencies = [
@@ -274,7 +1318,13 @@ dependencies = [
         fs::write(&pyproject_path, pyproject_content).unwrap();
 
         let result = detect_version_from_pyproject_toml(pyproject_path.to_str().unwrap());
-        assert_eq!(result, Some(Version::new(4, 2)));
+        assert_eq!(
+            result,
+            Some(DetectedVersion::new(
+                Version::new(4, 2),
+                "pyproject.toml (project.dependencies)"
+            ))
+        );
     }
 
     #[test]
@@ -293,16 +1343,16 @@ dependencies = [
 
         fs::write(&pyproject_path, pyproject_content).unwrap();
 
-        let result = detect_version_from_pyproject_toml(pyproject_path.to_str().unwrap());
+        let result = detect_version_from_pyproject_toml(pyproject_path.to_str().unwrap())
+            .map(|detected| detected.version);
         assert_eq!(result, None);
     }
 
     #[test]
-    fn test_detect_version_from_pyproject_unsupported_version() {
+    fn test_detect_version_from_pyproject_newest_version() {
         let temp_dir = tempdir().unwrap();
         let pyproject_path = temp_dir.path().join("pyproject.toml");
 
-        // Test with unsupported Django version
         let pyproject_content = r#"
 [project]
 dependencies = [
@@ -313,7 +1363,1450 @@ dependencies = [
 
         fs::write(&pyproject_path, pyproject_content).unwrap();
 
-        let result = detect_version_from_pyproject_toml(pyproject_path.to_str().unwrap());
+        let result = detect_version_from_pyproject_toml(pyproject_path.to_str().unwrap())
+            .map(|detected| detected.version);
+        assert_eq!(result, Some(Version::new(6, 0)));
+    }
+
+    #[test]
+    fn test_target_version_choices_match_supported_versions() {
+        let args = Args::parse_from(["djade", "--target-version", "6.1", "file1.html"]);
+        assert_eq!(args.target_version, "6.1");
+
+        let result = Args::try_parse_from(["djade", "--target-version", "1.11", "file1.html"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_supported_versions_sorted() {
+        let versions: Vec<Version> = supported_versions().collect();
+        let mut sorted = versions.clone();
+        sorted.sort();
+        assert_eq!(versions, sorted);
+    }
+
+    #[test]
+    fn test_resolve_detected_version_supported() {
+        assert_eq!(
+            resolve_detected_version(Version::new(4, 2)),
+            Some(Version::new(4, 2))
+        );
+        assert_eq!(
+            resolve_detected_version(Version::new(6, 1)),
+            Some(Version::new(6, 1))
+        );
+    }
+
+    #[test]
+    fn test_resolve_detected_version_between_supported() {
+        assert_eq!(
+            resolve_detected_version(Version::new(4, 0)),
+            Some(Version::new(3, 2))
+        );
+    }
+
+    #[test]
+    fn test_resolve_detected_version_too_new() {
+        assert_eq!(
+            resolve_detected_version(Version::new(7, 0)),
+            Some(Version::new(6, 1))
+        );
+    }
+
+    #[test]
+    fn test_resolve_detected_version_too_old() {
+        assert_eq!(resolve_detected_version(Version::new(1, 11)), None);
+    }
+
+    #[test]
+    fn test_detect_version_from_requirements_txt() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(
+            temp_dir.path().join("requirements.txt"),
+            "requests>=2.0\ndjango>=4.2,<5.0  # LTS\n",
+        )
+        .unwrap();
+
+        let result = detect_version_from_requirements_files(temp_dir.path())
+            .map(|detected| detected.version);
+        assert_eq!(result, Some(Version::new(4, 2)));
+    }
+
+    #[test]
+    fn test_detect_version_from_requirements_include() {
+        let temp_dir = tempdir().unwrap();
+        fs::create_dir(temp_dir.path().join("requirements")).unwrap();
+        fs::write(
+            temp_dir.path().join("requirements.txt"),
+            "-r requirements/base.in\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("requirements/base.in"),
+            "# Base requirements\nDjango==5.1.3\n",
+        )
+        .unwrap();
+
+        let result = detect_version_from_requirements_files(temp_dir.path());
+        assert_eq!(
+            result,
+            Some(DetectedVersion::new(
+                Version::new(5, 1),
+                Path::new("requirements")
+                    .join("base.in")
+                    .display()
+                    .to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_detect_version_from_requirements_include_cycle() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("requirements.txt"), "-r other.txt\n").unwrap();
+        fs::write(
+            temp_dir.path().join("other.txt"),
+            "--requirement=requirements.txt\n",
+        )
+        .unwrap();
+
+        let result = detect_version_from_requirements_files(temp_dir.path())
+            .map(|detected| detected.version);
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_detect_version_from_requirements_pip_compile_lock() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(
+            temp_dir.path().join("requirements.lock"),
+            r#"#
+# This file is autogenerated by pip-compile with Python 3.12
+#
+asgiref==3.8.1 \
+    --hash=sha256:3e1e3ecc849832fe52ccf2cb6686b7a55f82bb1d6aee72a58826471390335e47
+    # via django
+django==4.2.16 \
+    --hash=sha256:1ddc333a16fc139fd253035a1606bb24261951bbc3a6ca256717fa06cc41a898
+    # via -r requirements.in
+"#,
+        )
+        .unwrap();
+
+        let result = detect_version_from_requirements_files(temp_dir.path())
+            .map(|detected| detected.version);
+        assert_eq!(result, Some(Version::new(4, 2)));
+    }
+
+    #[test]
+    fn test_detect_version_from_requirements_directory() {
+        let temp_dir = tempdir().unwrap();
+        fs::create_dir(temp_dir.path().join("requirements")).unwrap();
+        fs::write(
+            temp_dir.path().join("requirements/base.in"),
+            "django~=5.2\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("requirements/dev.in"),
+            "-r base.in\npytest\n",
+        )
+        .unwrap();
+
+        let result = detect_version_from_requirements_files(temp_dir.path())
+            .map(|detected| detected.version);
+        assert_eq!(result, Some(Version::new(5, 2)));
+    }
+
+    #[test]
+    fn test_detect_version_from_requirements_no_django() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(
+            temp_dir.path().join("requirements.txt"),
+            "requests>=2.0\n-e .\n",
+        )
+        .unwrap();
+
+        let result = detect_version_from_requirements_files(temp_dir.path())
+            .map(|detected| detected.version);
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_requirements_lines() {
+        assert_eq!(
+            requirements_lines("a  # comment\n# full comment\nb==1 \\\n  --hash=x\nc#d\n"),
+            vec!["a", "b==1    --hash=x", "c#d"]
+        );
+    }
+
+    #[test]
+    fn test_detect_version_from_setup_cfg() {
+        let temp_dir = tempdir().unwrap();
+        let setup_cfg_path = temp_dir.path().join("setup.cfg");
+        fs::write(
+            &setup_cfg_path,
+            r#"
+[metadata]
+name = example
+
+[options]
+packages = find:
+install_requires =
+    # Web framework
+    Django>=3.2
+    requests>=2.0
+python_requires = >=3.8
+"#,
+        )
+        .unwrap();
+
+        let result = detect_version_from_setup_cfg(setup_cfg_path.to_str().unwrap())
+            .map(|detected| detected.version);
+        assert_eq!(result, Some(Version::new(3, 2)));
+    }
+
+    #[test]
+    fn test_detect_version_from_setup_cfg_single_line() {
+        let temp_dir = tempdir().unwrap();
+        let setup_cfg_path = temp_dir.path().join("setup.cfg");
+        fs::write(
+            &setup_cfg_path,
+            "[options]\ninstall_requires = django>=4.2\n",
+        )
+        .unwrap();
+
+        let result = detect_version_from_setup_cfg(setup_cfg_path.to_str().unwrap())
+            .map(|detected| detected.version);
+        assert_eq!(result, Some(Version::new(4, 2)));
+    }
+
+    #[test]
+    fn test_detect_version_from_setup_cfg_blank_line() {
+        let temp_dir = tempdir().unwrap();
+        let setup_cfg_path = temp_dir.path().join("setup.cfg");
+        fs::write(
+            &setup_cfg_path,
+            "[options]\ninstall_requires =\n    requests\n\n    django>=4.2\n\npython_requires = >=3.8\n",
+        )
+        .unwrap();
+
+        let result = detect_version_from_setup_cfg(setup_cfg_path.to_str().unwrap())
+            .map(|detected| detected.version);
+        assert_eq!(result, Some(Version::new(4, 2)));
+    }
+
+    #[test]
+    fn test_detect_version_from_setup_cfg_other_section() {
+        let temp_dir = tempdir().unwrap();
+        let setup_cfg_path = temp_dir.path().join("setup.cfg");
+        fs::write(
+            &setup_cfg_path,
+            "[options.extras_require]\ninstall_requires =\n    django>=4.2\n",
+        )
+        .unwrap();
+
+        let result = detect_version_from_setup_cfg(setup_cfg_path.to_str().unwrap())
+            .map(|detected| detected.version);
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn test_detect_version_from_setup_py() {
+        let temp_dir = tempdir().unwrap();
+        let setup_py_path = temp_dir.path().join("setup.py");
+        fs::write(
+            &setup_py_path,
+            r#"from setuptools import setup
+
+setup(
+    name="example",
+    install_requires=[
+        "django[argon2]>=4.1",  # Web framework
+        'requests>=2.0',
+    ],
+)
+"#,
+        )
+        .unwrap();
+
+        let result = detect_version_from_setup_py(setup_py_path.to_str().unwrap())
+            .map(|detected| detected.version);
+        assert_eq!(result, Some(Version::new(4, 1)));
+    }
+
+    #[test]
+    fn test_detect_version_from_setup_py_dynamic() {
+        let temp_dir = tempdir().unwrap();
+        let setup_py_path = temp_dir.path().join("setup.py");
+        fs::write(
+            &setup_py_path,
+            "setup(install_requires=[\"django>=4.2\"] + extra_requires)\n",
+        )
+        .unwrap();
+        assert_eq!(
+            detect_version_from_setup_py(setup_py_path.to_str().unwrap()),
+            Some(DetectedVersion::new(Version::new(4, 2), "setup.py"))
+        );
+
+        fs::write(
+            &setup_py_path,
+            "setup(install_requires=read_requirements(\"django>=4.2\"))\n",
+        )
+        .unwrap();
+        assert_eq!(
+            detect_version_from_setup_py(setup_py_path.to_str().unwrap()),
+            None
+        );
+    }
+
+    fn pyproject_version(content: &str) -> Option<(Version, String)> {
+        find_django_version_in_pyproject(&toml::from_str(content).unwrap())
+    }
+
+    #[test]
+    fn test_find_django_version_in_pyproject_project_dependencies() {
+        assert_eq!(
+            pyproject_version("[project]\ndependencies = [\"django>=4.2\"]\n"),
+            Some((Version::new(4, 2), "project.dependencies".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_find_django_version_in_pyproject_poetry() {
+        let content = r#"
+[tool.poetry.dependencies]
+python = "^3.10"
+Django = "^4.2"
+"#;
+        assert_eq!(
+            pyproject_version(content),
+            Some((Version::new(4, 2), "tool.poetry.dependencies".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_find_django_version_in_pyproject_poetry_table() {
+        let content = r#"
+[tool.poetry.dependencies]
+django = { version = "~5.1.2", extras = ["argon2"] }
+"#;
+        assert_eq!(
+            pyproject_version(content),
+            Some((Version::new(5, 1), "tool.poetry.dependencies".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_find_django_version_in_pyproject_poetry_multiple_constraints() {
+        let content = r#"
+[tool.poetry.dependencies]
+django = [
+    { version = "^5.2", python = ">=3.10" },
+    { version = "^4.2", python = "<3.10" },
+]
+"#;
+        assert_eq!(
+            pyproject_version(content),
+            Some((Version::new(4, 2), "tool.poetry.dependencies".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_find_django_version_in_pyproject_poetry_group() {
+        let content = r#"
+[tool.poetry.group.test.dependencies]
+django = ">=4.1,<5.0"
+"#;
+        assert_eq!(
+            pyproject_version(content),
+            Some((
+                Version::new(4, 1),
+                "tool.poetry.group.test.dependencies".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_find_django_version_in_pyproject_optional_dependencies() {
+        let content = r#"
+[project]
+dependencies = ["requests>=2.0"]
+optional-dependencies.django = ["django>=3.2"]
+"#;
+        assert_eq!(
+            pyproject_version(content),
+            Some((
+                Version::new(3, 2),
+                "project.optional-dependencies.django".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_find_django_version_in_pyproject_dependency_groups() {
+        let content = r#"
+[dependency-groups]
+test = [{ include-group = "django" }, "pytest"]
+django = ["django>=5.2"]
+"#;
+        assert_eq!(
+            pyproject_version(content),
+            Some((Version::new(5, 2), "dependency-groups.django".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_find_django_version_in_pyproject_pdm() {
+        let content = r#"
+[tool.pdm.dev-dependencies]
+test = ["django~=4.2"]
+"#;
+        assert_eq!(
+            pyproject_version(content),
+            Some((
+                Version::new(4, 2),
+                "tool.pdm.dev-dependencies.test".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_find_django_version_in_pyproject_main_dependencies_first() {
+        let content = r#"
+[project]
+dependencies = ["django>=4.2"]
+
+[dependency-groups]
+dev = ["django>=5.2"]
+"#;
+        assert_eq!(
+            pyproject_version(content),
+            Some((Version::new(4, 2), "project.dependencies".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_poetry_constraint() {
+        assert_eq!(parse_poetry_constraint("^4.2"), Some(Version::new(4, 2)));
+        assert_eq!(parse_poetry_constraint("~4.2.1"), Some(Version::new(4, 2)));
+        assert_eq!(parse_poetry_constraint("~=5.1"), Some(Version::new(5, 1)));
+        assert_eq!(parse_poetry_constraint("4.2.*"), Some(Version::new(4, 2)));
+        assert_eq!(parse_poetry_constraint("5.0.3"), Some(Version::new(5, 0)));
+        assert_eq!(parse_poetry_constraint("^6"), Some(Version::new(6, 0)));
+        assert_eq!(
+            parse_poetry_constraint(">= 4.2, < 5.0"),
+            Some(Version::new(4, 2))
+        );
+        assert_eq!(
+            parse_poetry_constraint("^5.2 || ^4.2"),
+            Some(Version::new(4, 2))
+        );
+        assert_eq!(parse_poetry_constraint("*"), None);
+        assert_eq!(parse_poetry_constraint("<5.0"), None);
+    }
+
+    #[test]
+    fn test_target_version_locked() {
+        let args = Args::parse_from(["djade", "--target-version", "locked", "file1.html"]);
+        assert_eq!(args.target_version, "locked");
+    }
+
+    #[test]
+    fn test_parse_lock_file_uv() {
+        let content = r#"
+version = 1
+requires-python = ">=3.12"
+
+[[package]]
+name = "asgiref"
+version = "3.8.1"
+source = { registry = "https://pypi.org/simple" }
+
+[[package]]
+name = "django"
+version = "5.1.4"
+source = { registry = "https://pypi.org/simple" }
+dependencies = [
+    { name = "asgiref" },
+]
+"#;
+        assert_eq!(parse_lock_file(content), Some(Version::new(5, 1)));
+    }
+
+    #[test]
+    fn test_parse_lock_file_poetry() {
+        let content = r#"
+[[package]]
+name = "Django"
+version = "4.2.17"
+description = "A high-level Python web framework."
+optional = false
+python-versions = ">=3.8"
+
+[metadata]
+lock-version = "2.0"
+"#;
+        assert_eq!(parse_lock_file(content), Some(Version::new(4, 2)));
+    }
+
+    #[test]
+    fn test_parse_lock_file_no_django() {
+        let content = "[[package]]\nname = \"requests\"\nversion = \"2.32.3\"\n";
+        assert_eq!(parse_lock_file(content), None);
+    }
+
+    #[test]
+    fn test_detect_version_from_lock_files() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(
+            temp_dir.path().join("pdm.lock"),
+            "[[package]]\nname = \"django\"\nversion = \"5.2.1\"\n",
+        )
+        .unwrap();
+
+        let result = detect_version_from_lock_files(temp_dir.path());
+        assert_eq!(
+            result,
+            Some(DetectedVersion::new(Version::new(5, 2), "pdm.lock"))
+        );
+    }
+
+    #[test]
+    fn test_parse_release_version() {
+        assert_eq!(parse_release_version("5.2.1"), Some(Version::new(5, 2)));
+        assert_eq!(parse_release_version("6.0a1"), Some(Version::new(6, 0)));
+        assert_eq!(parse_release_version("6"), None);
+    }
+
+    #[test]
+    fn test_find_django_in_virtualenv() {
+        let temp_dir = tempdir().unwrap();
+        let django_dir = temp_dir
+            .path()
+            .join(".venv/lib/python3.12/site-packages/django");
+        fs::create_dir_all(&django_dir).unwrap();
+        fs::write(
+            django_dir.join("__init__.py"),
+            "from django.utils.version import get_version\n\nVERSION = (4, 2, 17, \"final\", 0)\n",
+        )
+        .unwrap();
+
+        let result = find_django_in_virtualenv(&temp_dir.path().join(".venv"));
+        assert_eq!(result, Some(Version::new(4, 2)));
+    }
+
+    #[test]
+    fn test_find_django_in_virtualenv_windows() {
+        let temp_dir = tempdir().unwrap();
+        let django_dir = temp_dir.path().join("venv/Lib/site-packages/django");
+        fs::create_dir_all(&django_dir).unwrap();
+        fs::write(
+            django_dir.join("__init__.py"),
+            "VERSION = (5, 0, 0, 'final', 0)\n",
+        )
+        .unwrap();
+
+        let result = find_django_in_virtualenv(&temp_dir.path().join("venv"));
+        assert_eq!(result, Some(Version::new(5, 0)));
+    }
+
+    #[test]
+    fn test_find_django_in_virtualenv_missing() {
+        let temp_dir = tempdir().unwrap();
+        let result = find_django_in_virtualenv(&temp_dir.path().join(".venv"));
         assert_eq!(result, None);
     }
+
+    #[test]
+    fn test_resolve_target_version_explicit() {
+        assert_eq!(
+            resolve_target_version("5.2"),
+            Some(ResolvedVersion {
+                version: Version::new(5, 2),
+                source: "--target-version".to_string(),
+                detected: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_show_config_args() {
+        let args = Args::parse_from([
+            "djade",
+            "show-config",
+            "--target-version",
+            "4.2",
+            "templates/engine.html",
+        ]);
+        assert!(args.filenames.is_empty());
+        assert_eq!(args.target_version, "4.2");
+        match args.command {
+            Some(Command::ShowConfig { path }) => assert_eq!(path, "templates/engine.html"),
+            _ => panic!("Expected show-config command"),
+        }
+    }
+
+    #[test]
+    fn test_enable_fixer_args() {
+        let args = Args::parse_from([
+            "djade",
+            "--enable-fixer",
+            "multiline-tags",
+            "templates/engine.html",
+        ]);
+        assert_eq!(args.enable_fixers, vec!["multiline-tags"]);
+        assert!(selected_fixers(&args).contains(&Fixer::MultilineTags));
+    }
+
+    #[test]
+    fn test_enable_fixer_args_default() {
+        let args = Args::parse_from(["djade", "templates/engine.html"]);
+        assert_eq!(selected_fixers(&args), Fixer::DEFAULT.to_vec());
+    }
+
+    #[test]
+    fn test_enable_fixer_args_not_opt_in() {
+        let result = Args::try_parse_from([
+            "djade",
+            "--enable-fixer",
+            "length-is",
+            "templates/engine.html",
+        ]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_version_display() {
+        assert_eq!(Version::new(5, 2).to_string(), "5.2");
+    }
+
+    // main
+
+    #[test]
+    fn test_main_impl_one_already_formatted() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{{ name }}\n").unwrap();
+
+        // Capture stderr
+        let mut buffer = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut buffer);
+
+        // Run the main function with our non-UTF-8 file
+        let args = Args {
+            filenames: vec![file_path.to_str().unwrap().to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let returncode = main_impl(&args, &mut writer);
+
+        assert_eq!(returncode, 0);
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(output, "1 file already formatted\n");
+    }
+
+    #[test]
+    fn test_main_impl_one_reformatted() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{{name}}").unwrap();
+
+        // Capture stderr
+        let mut buffer = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut buffer);
+
+        // Run the main function with our non-UTF-8 file
+        let args = Args {
+            filenames: vec![file_path.to_str().unwrap().to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let returncode = main_impl(&args, &mut writer);
+
+        assert_eq!(returncode, 1);
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(output, "1 file reformatted\n");
+
+        // Verify the file was changed
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "{{ name }}\n");
+    }
+
+    #[test]
+    fn test_main_impl_one_non_utf_8_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("non_utf8.txt");
+
+        // Create a file with non-UTF-8 content
+        let mut file = File::create(&file_path).unwrap();
+        file.write_all(&[0xFF, 0xFE, 0xFD]).unwrap();
+
+        // Capture stderr
+        let mut buffer = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut buffer);
+
+        // Run the main function with our non-UTF-8 file
+        let args = Args {
+            filenames: vec![file_path.to_str().unwrap().to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let returncode = main_impl(&args, &mut writer);
+
+        assert_eq!(returncode, 1);
+
+        let output = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with("non_utf8.txt is non-UTF-8 (not supported)"));
+    }
+
+    #[test]
+    fn test_main_impl_syntax_error() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.jinja");
+        fs::write(&file_path, "{{ name }}\n{{ }}\n").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut buffer);
+        let args = Args {
+            filenames: vec![path.to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let returncode = main_impl(&args, &mut writer);
+
+        assert_eq!(returncode, 1);
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            format!(
+                "Error formatting {}: empty variable tag at line 2, column 1\n",
+                path
+            )
+        );
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "{{ name }}\n{{ }}\n"
+        );
+    }
+
+    #[test]
+    fn test_main_impl_diagnostics_empty_tag() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{{name}}\n{{ }}\n").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut buffer);
+        let args = Args {
+            filenames: vec![path.to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let returncode = main_impl(&args, &mut writer);
+
+        assert_eq!(returncode, 1);
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            format!(
+                "Warning: {}: empty variable tag at line 2, column 1\n1 file reformatted\n",
+                path
+            )
+        );
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "{{ name }}\n{{ }}\n"
+        );
+    }
+
+    #[test]
+    fn test_main_impl_diagnostics_warn() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{% if a %}\n{{ name }}\n").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut buffer);
+        let args = Args {
+            filenames: vec![path.to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let returncode = main_impl(&args, &mut writer);
+
+        assert_eq!(returncode, 0);
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            format!(
+                "Warning: {}: unclosed 'if' at line 1, column 1\n1 file already formatted\n",
+                path
+            )
+        );
+    }
+
+    #[test]
+    fn test_main_impl_diagnostics_unparsed_variable() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "<p>\n  {{ a b }}\n</p>\n").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut buffer);
+        let args = Args {
+            filenames: vec![path.to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: true,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let returncode = main_impl(&args, &mut writer);
+
+        assert_eq!(returncode, 1);
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            format!(
+                "Error: {}: could not parse the remainder: ' b' from 'a b' at line 2, column 3\n1 file already formatted\n",
+                path
+            )
+        );
+    }
+
+    #[test]
+    fn test_main_impl_diagnostics_check_errors() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{{ name }}\n{% endblock %}\n").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut buffer);
+        let args = Args {
+            filenames: vec![path.to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: true,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let returncode = main_impl(&args, &mut writer);
+
+        assert_eq!(returncode, 1);
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            format!(
+                "Error: {}: unexpected 'endblock' at line 2, column 1\n1 file already formatted\n",
+                path
+            )
+        );
+    }
+
+    #[test]
+    fn test_main_impl_diagnostics_glued_operator() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "<p>\n  {% if a==b %}x{% endif %}\n</p>\n").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut buffer);
+        let args = Args {
+            filenames: vec![path.to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: true,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let returncode = main_impl(&args, &mut writer);
+
+        assert_eq!(returncode, 1);
+        let output = String::from_utf8(buffer).unwrap();
+        assert_eq!(
+            output,
+            format!(
+                "Error: {}: could not parse the remainder: '==b' from 'a==b' at line 2, column 3\nWould reformat: {}\n1 file would be reformatted\n",
+                path, path
+            )
+        );
+    }
+
+    #[test]
+    fn test_main_impl_enable_fixer() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{% if a\n  and b %}\n{% endif %}\n").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut buffer);
+        let args = Args {
+            filenames: vec![path.to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: false,
+            enable_fixers: vec!["multiline-tags".to_string()],
+            dialect: "auto".to_string(),
+        };
+
+        let returncode = main_impl(&args, &mut writer);
+
+        assert_eq!(returncode, 1);
+        assert_eq!(String::from_utf8(buffer).unwrap(), "1 file reformatted\n");
+        assert_eq!(
+            fs::read_to_string(&file_path).unwrap(),
+            "{% if a and b %}\n{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_main_impl_check_option() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{{name}}").unwrap();
+
+        let mut buffer = Vec::new();
+        let mut writer = std::io::Cursor::new(&mut buffer);
+
+        let args = Args {
+            filenames: vec![file_path.to_str().unwrap().to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: true,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let returncode = main_impl(&args, &mut writer);
+
+        assert_eq!(returncode, 1);
+        let output = String::from_utf8(buffer).unwrap();
+        // split into lines
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("Would reformat: "));
+        assert!(lines[0].ends_with("tank-engine.html"));
+        assert_eq!(lines[1], "1 file would be reformatted");
+
+        // Verify the file wasn't actually changed
+        let content = fs::read_to_string(&file_path).unwrap();
+        assert_eq!(content, "{{name}}");
+    }
+
+    #[test]
+    fn test_main_impl_auto_version_with_pyproject() {
+        let dir = tempdir().unwrap();
+        let old_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        let pyproject_content = r#"
+[project]
+dependencies = [
+    "django>=4.2,<5.0",
+    "requests>=2.0",
+]
+"#;
+        fs::write("pyproject.toml", pyproject_content).unwrap();
+
+        // Create a test template file
+        let template_content = "{% block content %}\nHello\n{% endblock %}";
+        let template_path = dir.path().join("test.html");
+        fs::write(&template_path, template_content).unwrap();
+
+        let args = Args {
+            filenames: vec![template_path.to_str().unwrap().to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let mut output = Vec::new();
+        let exit_code = main_impl(&args, &mut output);
+
+        assert_eq!(exit_code, 1);
+        let output_str = String::from_utf8(output).unwrap();
+        assert!(output_str.contains("1 file reformatted"));
+
+        let reformatted_content = fs::read_to_string(&template_path).unwrap();
+        assert!(reformatted_content.contains("{% endblock content %}"));
+
+        std::env::set_current_dir(old_dir).unwrap();
+    }
+
+    #[test]
+    fn test_main_impl_auto_version_without_pyproject() {
+        let dir = tempdir().unwrap();
+        let old_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        let template_content = "{% block content %}\nHello\n{% endblock %}";
+        let template_path = dir.path().join("test.html");
+        fs::write(&template_path, template_content).unwrap();
+
+        let args = Args {
+            filenames: vec![template_path.to_str().unwrap().to_string()],
+            command: None,
+            target_version: "auto".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let mut output = Vec::new();
+        let exit_code = main_impl(&args, &mut output);
+
+        assert_eq!(exit_code, 1);
+        let output_str = String::from_utf8(output).unwrap();
+        assert!(output_str.contains("1 file reformatted"));
+
+        let reformatted_content = fs::read_to_string(&template_path).unwrap();
+        assert!(reformatted_content.contains("{% endblock content %}"));
+
+        std::env::set_current_dir(old_dir).unwrap();
+    }
+
+    #[test]
+    fn test_main_impl_explicit_version() {
+        let dir = tempdir().unwrap();
+
+        let template_content = "{% block content %}\nHello\n{% endblock %}";
+        let template_path = dir.path().join("test.html");
+        fs::write(&template_path, template_content).unwrap();
+
+        let args = Args {
+            filenames: vec![template_path.to_str().unwrap().to_string()],
+            command: None,
+            target_version: "4.2".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+
+        let mut output = Vec::new();
+        let exit_code = main_impl(&args, &mut output);
+
+        assert_eq!(exit_code, 1);
+        let output_str = String::from_utf8(output).unwrap();
+        assert!(output_str.contains("1 file reformatted"));
+
+        let reformatted_content = fs::read_to_string(&template_path).unwrap();
+        assert!(reformatted_content.contains("{% endblock content %}"));
+    }
+
+    // show_config
+
+    #[test]
+    fn test_show_config_explicit_version() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{{ name }}\n").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let args = Args {
+            command: None,
+            filenames: vec![],
+            target_version: "4.1".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        let returncode = show_config(&args, path, &mut output, &mut errors);
+
+        assert_eq!(returncode, 0);
+        assert_eq!(String::from_utf8(errors).unwrap(), "");
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "Template: {}
+Dialect: django (default)
+Target version: 4.1 (from --target-version)
+Fixers:
+  multiline-comments (opt-in): inactive
+  multiline-tags (opt-in): inactive
+  length-is (Django 4.2+): inactive
+  empty-json-script (Django 4.1+): active
+  translation-tags (Django 3.1+): active
+  ifequal-tags (Django 3.1+): active
+  static-load-tags (Django 2.1+): active
+  assignments (always): active
+Formatters:
+  tag-spacing
+  leading-trailing-whitespace
+  load-tags
+  if-expressions
+  endblock-labels
+  top-level-block-indentation
+  top-level-block-spacing
+Exclusions:
+  {{% verbatim %}} ... {{% endverbatim %}} contents
+  {{% comment %}} ... {{% endcomment %}} contents
+",
+                path
+            )
+        );
+    }
+
+    #[test]
+    fn test_show_config_jinja2() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("name.jinja");
+        fs::write(&file_path, "{{ name }}\n").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        let args = Args {
+            command: None,
+            filenames: vec![],
+            target_version: "4.1".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        let returncode = show_config(&args, path, &mut output, &mut errors);
+
+        assert_eq!(returncode, 0);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Dialect: jinja2 (from file extension)\n"));
+        assert!(output.contains("  assignments (always): inactive\n"));
+        assert!(output.ends_with(
+            "Formatters:
+  tag-spacing
+  leading-trailing-whitespace
+  endblock-labels
+Exclusions:
+  {% raw %} ... {% endraw %} contents
+  {{ ... }} and {# ... #} tags with multi-line contents
+"
+        ));
+    }
+
+    #[test]
+    fn test_resolve_dialect() {
+        assert_eq!(resolve_dialect("auto", "a.html"), Dialect::Django);
+        assert_eq!(resolve_dialect("auto", "a.jinja"), Dialect::Jinja2);
+        assert_eq!(resolve_dialect("auto", "a.html.jinja2"), Dialect::Jinja2);
+        assert_eq!(resolve_dialect("auto", "a.j2"), Dialect::Jinja2);
+        assert_eq!(resolve_dialect("auto", "-"), Dialect::Django);
+        assert_eq!(resolve_dialect("jinja2", "a.html"), Dialect::Jinja2);
+        assert_eq!(resolve_dialect("django", "a.j2"), Dialect::Django);
+    }
+
+    #[test]
+    fn test_show_config_missing_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("missing.html");
+
+        let args = Args {
+            command: None,
+            filenames: vec![],
+            target_version: "4.1".to_string(),
+            check: false,
+            enable_fixers: vec![],
+            dialect: "auto".to_string(),
+        };
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        let returncode = show_config(&args, file_path.to_str().unwrap(), &mut output, &mut errors);
+
+        assert_eq!(returncode, 1);
+        assert!(output.is_empty());
+        assert!(
+            String::from_utf8(errors)
+                .unwrap()
+                .starts_with("Error reading ")
+        );
+    }
+
+    // parse
+
+    #[test]
+    fn test_parse_args() {
+        let args = Args::parse_from(["djade", "parse", "--json", "templates/engine.html"]);
+        match args.command {
+            Some(Command::Parse { path, json }) => {
+                assert_eq!(path, "templates/engine.html");
+                assert!(json);
+            }
+            _ => panic!("Expected parse command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_args_lines() {
+        let args = Args::parse_from(["djade", "parse", "templates/engine.html"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Parse { json: false, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{% if a %}\n  {{ b }}\n{% endif %}{# c #}").unwrap();
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        let returncode = parse(
+            file_path.to_str().unwrap(),
+            Dialect::Django,
+            false,
+            &mut output,
+            &mut errors,
+        );
+
+        assert_eq!(returncode, 0);
+        assert!(errors.is_empty());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "1:1 block \"{% if a %}\"
+1:11   text \"\\n  \"
+2:3   variable \"{{ b }}\"
+2:10   text \"\\n\"
+3:1 block \"{% endif %}\"
+3:12 comment \"{# c #}\"
+"
+        );
+    }
+
+    /// A writer that fails with the given kind of error.
+    struct FailingWriter(io::ErrorKind);
+
+    impl io::Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(self.0))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_broken_pipe() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{{ a }}").unwrap();
+        let path = file_path.to_str().unwrap();
+
+        for json in [true, false] {
+            let mut errors = Vec::new();
+            let mut writer = FailingWriter(io::ErrorKind::BrokenPipe);
+
+            let returncode = parse(path, Dialect::Django, json, &mut writer, &mut errors);
+
+            assert_eq!(returncode, 0);
+            assert!(errors.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_parse_write_error() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{{ a }}").unwrap();
+        let mut errors = Vec::new();
+        let mut writer = FailingWriter(io::ErrorKind::PermissionDenied);
+
+        let returncode = parse(
+            file_path.to_str().unwrap(),
+            Dialect::Django,
+            true,
+            &mut writer,
+            &mut errors,
+        );
+
+        assert_eq!(returncode, 1);
+        assert!(
+            String::from_utf8(errors)
+                .unwrap()
+                .starts_with("Error writing tokens: ")
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(
+            &file_path,
+            "{% if a %}{{ b|default:'c' }}{% endif %}\n{# d #}",
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        let returncode = parse(
+            file_path.to_str().unwrap(),
+            Dialect::Django,
+            true,
+            &mut output,
+            &mut errors,
+        );
+
+        assert_eq!(returncode, 0);
+        assert!(errors.is_empty());
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {
+                    "type": "block",
+                    "bits": ["if", "a"],
+                    "span": {"start": 0, "end": 10, "line": 1, "column": 1},
+                    "depth": 0,
+                    "parent": null,
+                },
+                {
+                    "type": "variable",
+                    "filter_expression": {
+                        "var": {"type": "variable", "value": "b"},
+                        "filters": [
+                            {"name": "default", "arg": {"type": "constant", "value": "'c'"}},
+                        ],
+                    },
+                    "span": {"start": 10, "end": 29, "line": 1, "column": 11},
+                    "depth": 1,
+                    "parent": 0,
+                },
+                {
+                    "type": "block",
+                    "bits": ["endif"],
+                    "span": {"start": 29, "end": 40, "line": 1, "column": 30},
+                    "depth": 0,
+                    "parent": null,
+                },
+                {
+                    "type": "text",
+                    "contents": "\n",
+                    "span": {"start": 40, "end": 41, "line": 1, "column": 41},
+                    "depth": 0,
+                    "parent": null,
+                },
+                {
+                    "type": "comment",
+                    "contents": "d",
+                    "span": {"start": 41, "end": 48, "line": 2, "column": 1},
+                    "depth": 0,
+                    "parent": null,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_unparsed_variable() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{{ a b }}").unwrap();
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        let returncode = parse(
+            file_path.to_str().unwrap(),
+            Dialect::Django,
+            true,
+            &mut output,
+            &mut errors,
+        );
+
+        assert_eq!(returncode, 0);
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            json[0]["filter_expression"]["var"],
+            serde_json::json!({"type": "unparsed", "value": "a b"})
+        );
+    }
+
+    #[test]
+    fn test_parse_syntax_error() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("tank-engine.html");
+        fs::write(&file_path, "{% %}").unwrap();
+        let path = file_path.to_str().unwrap();
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        let returncode = parse(path, Dialect::Jinja2, true, &mut output, &mut errors);
+
+        assert_eq!(returncode, 1);
+        assert!(output.is_empty());
+        assert_eq!(
+            String::from_utf8(errors).unwrap(),
+            format!(
+                "Error parsing {}: empty block tag at line 1, column 1\n",
+                path
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_missing_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("missing.html");
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        let returncode = parse(
+            file_path.to_str().unwrap(),
+            Dialect::Django,
+            true,
+            &mut output,
+            &mut errors,
+        );
+
+        assert_eq!(returncode, 1);
+        assert!(output.is_empty());
+        assert!(
+            String::from_utf8(errors)
+                .unwrap()
+                .starts_with("Error reading ")
+        );
+    }
 }
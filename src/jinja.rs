@@ -0,0 +1,428 @@
+// Lexer for Jinja2 templates, based on Jinja’s:
+// https://github.com/pallets/jinja/blob/main/src/jinja2/lexer.py
+// It produces the same tokens as the Django lexer, so both dialects share the
+// formatters and final build.
+
+use crate::{
+    DjadeError, Expression, FilterExpression, Markers, Span, SpanTracker, Token, smart_split,
+};
+use std::borrow::Cow;
+
+pub(crate) fn lex(template_string: &str) -> Result<Vec<Token<'_>>, DjadeError> {
+    let mut result = Vec::new();
+    let mut spans = SpanTracker::new();
+    let mut last_end = 0;
+    let mut from = 0;
+
+    let mut push_token = |start: usize, end: usize, in_tag: bool| {
+        let token_string = &template_string[start..end];
+        let span = spans.span(template_string, start, end);
+        let token = if in_tag {
+            create_token(token_string, span).map_err(|message| DjadeError::Syntax {
+                message: message.to_string(),
+                location: span.location,
+            })?
+        } else {
+            Token::Text {
+                contents: Cow::Borrowed(token_string),
+                span,
+            }
+        };
+        let raw = matches!(&token, Token::Block { bits, .. } if bits[0] == "raw");
+        result.push(token);
+        Ok::<_, DjadeError>(raw)
+    };
+
+    while let Some(offset) = template_string[from..].find('{') {
+        let start = from + offset;
+        let (closer, kind) = match template_string.as_bytes().get(start + 1) {
+            Some(b'%') => ("%}", "block"),
+            Some(b'{') => ("}}", "variable"),
+            Some(b'#') => ("#}", "comment"),
+            _ => {
+                from = start + 1;
+                continue;
+            }
+        };
+        let Some(end) = find_tag_end(template_string, start + 2, closer) else {
+            return Err(DjadeError::Syntax {
+                message: format!("unclosed {} tag", kind),
+                location: Span::empty_at(template_string, start).location,
+            });
+        };
+
+        if start > last_end {
+            push_token(last_end, start, false)?;
+        }
+        last_end = end;
+
+        if push_token(start, end, true)? {
+            // Jinja’s lexer skips to the next {% endraw %}.
+            let Some((endraw_start, endraw_end)) = find_endraw(template_string, end) else {
+                return Err(DjadeError::Syntax {
+                    message: "unclosed 'raw'".to_string(),
+                    location: Span::empty_at(template_string, start).location,
+                });
+            };
+            if endraw_start > end {
+                push_token(end, endraw_start, false)?;
+            }
+            push_token(endraw_start, endraw_end, true)?;
+            last_end = endraw_end;
+        }
+        from = last_end;
+    }
+
+    if last_end < template_string.len() {
+        push_token(last_end, template_string.len(), false)?;
+    }
+
+    Ok(result)
+}
+
+/// Find the end of a tag whose contents start at `from`: just after the first
+/// `closer` outside of strings and brackets. Comments end at the first
+/// closer.
+fn find_tag_end(template_string: &str, from: usize, closer: &str) -> Option<usize> {
+    if closer == "#}" {
+        return template_string[from..]
+            .find(closer)
+            .map(|offset| from + offset + closer.len());
+    }
+    let bytes = template_string.as_bytes();
+    let mut depth = 0usize;
+    let mut i = from;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'"' | b'\'') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' if depth > 0 => depth -= 1,
+            _ if depth == 0 && bytes[i..].starts_with(closer.as_bytes()) => {
+                return Some(i + closer.len());
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Find the start and end of the next `{% endraw %}` tag at or after `from`.
+fn find_endraw(template_string: &str, mut from: usize) -> Option<(usize, usize)> {
+    while let Some(offset) = template_string[from..].find("{%") {
+        let start = from + offset;
+        let rest = &template_string[start + 2..];
+        let rest = rest.strip_prefix(['-', '+']).unwrap_or(rest);
+        if let Some(rest) = rest.trim_start().strip_prefix("endraw") {
+            let rest = rest.trim_start();
+            let rest = rest.strip_prefix(['-', '+']).unwrap_or(rest);
+            if let Some(rest) = rest.strip_prefix("%}") {
+                return Some((start, template_string.len() - rest.len()));
+            }
+        }
+        from = start + 2;
+    }
+    None
+}
+
+fn create_token(token_string: &str, span: Span) -> Result<Token<'_>, &'static str> {
+    let mut inner = &token_string[2..token_string.len() - 2];
+    let mut markers = Markers::default();
+    if let Some(marker @ ('-' | '+')) = inner.chars().next() {
+        markers.start = Some(marker);
+        inner = &inner[1..];
+    }
+    if let Some(marker @ ('-' | '+')) = inner.chars().next_back() {
+        markers.end = Some(marker);
+        inner = &inner[..inner.len() - 1];
+    }
+    let content = inner.trim();
+    // Multi-line variables and comments keep their layout. Multi-line block
+    // tags keep the layout of their arguments.
+    let multiline = inner.contains('\n');
+
+    if token_string.starts_with("{%") {
+        if content.is_empty() {
+            return Err("empty block tag");
+        }
+        let (name, rest) = split_name(content);
+        let mut bits = vec![Cow::Borrowed(name)];
+        if rest.contains('\n') {
+            bits.push(Cow::Borrowed(rest));
+        } else {
+            bits.extend(smart_split(rest).map(Cow::Borrowed));
+        }
+        Ok(Token::Block {
+            bits,
+            markers,
+            span,
+        })
+    } else if multiline {
+        Ok(Token::Text {
+            contents: Cow::Borrowed(token_string),
+            span,
+        })
+    } else if token_string.starts_with("{{") {
+        if content.is_empty() {
+            return Err("empty variable tag");
+        }
+        let parts: Vec<&str> = smart_split(content).collect();
+        let expr = match parts[..] {
+            [part] => Cow::Borrowed(part),
+            _ => Cow::Owned(parts.join(" ")),
+        };
+        Ok(Token::Variable {
+            filter_expression: FilterExpression {
+                var: Expression::Unparsed(expr),
+                filters: Vec::new(),
+            },
+            markers,
+            span,
+        })
+    } else {
+        Ok(Token::Comment {
+            contents: Cow::Borrowed(content),
+            markers,
+            span,
+        })
+    }
+}
+
+/// Split a block tag’s contents into its name and arguments. Like Jinja, the
+/// name is an identifier, so may be followed directly by its arguments, as in
+/// `if(a)`.
+fn split_name(content: &str) -> (&str, &str) {
+    let end = content
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(content.len());
+    let end = match end {
+        0 => content.find(char::is_whitespace).unwrap_or(content.len()),
+        _ => end,
+    };
+    (&content[..end], content[end..].trim_start())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dialect, FormatOptions, format_str};
+
+    fn format(content: &str) -> String {
+        let options = FormatOptions {
+            dialect: Dialect::Jinja2,
+            ..FormatOptions::default()
+        };
+        format_str(content, &options).unwrap()
+    }
+
+    fn block_bits(template_string: &str) -> Vec<Vec<String>> {
+        lex(template_string)
+            .unwrap()
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Block { bits, .. } => Some(bits.into_iter().map(Cow::into_owned).collect()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // lex
+
+    #[test]
+    fn test_lex_markers() {
+        let tokens = lex("{%- if x +%}{{- y }}{# z -#}").unwrap();
+        let markers: Vec<Markers> = tokens
+            .iter()
+            .map(|token| match token {
+                Token::Block { markers, .. }
+                | Token::Variable { markers, .. }
+                | Token::Comment { markers, .. } => *markers,
+                Token::Text { .. } => Markers::default(),
+            })
+            .collect();
+        assert_eq!(
+            markers,
+            vec![
+                Markers {
+                    start: Some('-'),
+                    end: Some('+'),
+                },
+                Markers {
+                    start: Some('-'),
+                    end: None,
+                },
+                Markers {
+                    start: None,
+                    end: Some('-'),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_closer_in_string() {
+        assert_eq!(
+            block_bits("{% set x = '%}' %}"),
+            vec![vec!["set", "x", "=", "'%}'"]]
+        );
+    }
+
+    #[test]
+    fn test_lex_closer_in_brackets() {
+        let tokens = lex("{{ {'a': {'b': 1}} }}").unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].span().end, 21);
+    }
+
+    #[test]
+    fn test_lex_raw() {
+        let tokens = lex("{% raw %}{{ x }}{% if %}{%- endraw %}").unwrap();
+        assert!(matches!(
+            &tokens[1],
+            Token::Text { contents, .. } if contents == "{{ x }}{% if %}"
+        ));
+        assert_eq!(
+            block_bits("{% raw %}{% endraw %}"),
+            vec![vec!["raw"], vec!["endraw"]]
+        );
+    }
+
+    #[test]
+    fn test_lex_multiline_block() {
+        assert_eq!(
+            block_bits("{% set x = [\n    1,\n    2,\n] %}"),
+            vec![vec!["set", "x = [\n    1,\n    2,\n]"]]
+        );
+    }
+
+    #[test]
+    fn test_lex_name_before_brackets() {
+        assert_eq!(
+            block_bits("{% if(a) %}{% call(x) m(\n  x) %}"),
+            vec![vec!["if", "(a)"], vec!["call", "(x) m(\n  x)"]]
+        );
+    }
+
+    #[test]
+    fn test_lex_locations() {
+        let tokens = lex("a\n{% if x %}\n  {{ y }}").unwrap();
+        let location = tokens[3].span().location;
+        assert_eq!((location.line, location.column), (3, 3));
+    }
+
+    #[test]
+    fn test_lex_unclosed_tag() {
+        let error = lex("a\n  {{ x").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unclosed variable tag at line 2, column 3"
+        );
+    }
+
+    #[test]
+    fn test_lex_unclosed_raw() {
+        let error = lex("{% raw %}{{ x }}").unwrap_err();
+        assert_eq!(error.to_string(), "unclosed 'raw' at line 1, column 1");
+    }
+
+    #[test]
+    fn test_lex_empty_block_tag() {
+        let error = lex("{%- %}").unwrap_err();
+        assert_eq!(error.to_string(), "empty block tag at line 1, column 1");
+    }
+
+    // format
+
+    #[test]
+    fn test_format_tag_spacing() {
+        assert_eq!(
+            format("{%if  x%}{{y|upper}}{%endif%}"),
+            "{% if x %}{{ y|upper }}{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_markers() {
+        assert_eq!(
+            format("{%-if x+%}{{-  y  -}}{#-z-#}{%  endif  -%}"),
+            "{%- if x +%}{{- y -}}{#- z -#}{% endif -%}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_expressions() {
+        assert_eq!(
+            format("{{  foo(a,  'b  c')  |  default(1) }}"),
+            "{{ foo(a, 'b  c') | default(1) }}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_multiline_tags_unchanged() {
+        let content = "{%  set x = {\n    'a': 1,\n} %}\n{{ y\n   |z }}\n{# a\n  b #}\n";
+        assert_eq!(
+            format(content),
+            "{% set x = {\n    'a': 1,\n} %}\n{{ y\n   |z }}\n{# a\n  b #}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_multiline_padding() {
+        let content = "{{\n  y\n}}{#\n  z\n#}\n";
+        assert_eq!(format(content), content);
+        assert_eq!(
+            format("{%\n  if a\n%}{% endif %}"),
+            "{% if a %}{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_name_before_brackets() {
+        assert_eq!(
+            format("{% if(a\n  and b) %}{% endif %}"),
+            "{% if (a\n  and b) %}{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_idempotent() {
+        for content in [
+            "{%if  x%}{{y|upper}}{%endif%}",
+            "{%-if x+%}{{-  y  -}}{#-z-#}{%  endif  -%}",
+            "{% if(a) %}{% endif %}",
+            "{% if\t(a\n  and b) %}{% endif %}",
+            "{%\tset x = {\n    'a': 1,\n}\n%}",
+            "{% macro\tf(a,\n\tb) %}{% endmacro %}",
+            "{{\n  y\n}}{#\n  z\n#}",
+            "{% block a %}\n{% for b in c %}{% else %}{% endfor %}\n{% endblock a %}",
+        ] {
+            let formatted = format(content);
+            assert_eq!(format(&formatted), formatted, "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn test_format_endblock_labels() {
+        assert_eq!(
+            format("{% block a %}x{% endblock a %}\n{% block b %}\n{% endblock %}\n"),
+            "{% block a %}x{% endblock %}\n{% block b %}\n{% endblock b %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_raw_unchanged() {
+        let content = "{% raw %}{%if x%}{{y}}{% endraw %}\n";
+        assert_eq!(format(content), content);
+    }
+
+    #[test]
+    fn test_format_no_django_rules() {
+        let content = "{% extends 'base.html' %}\n{% load b a %}\n{% if a==b %}{% endif %}\n";
+        assert_eq!(format(content), content);
+    }
+}
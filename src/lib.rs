@@ -0,0 +1,4495 @@
+//! A Django template formatter.
+//!
+//! Format templates in-process with [`format_str`], or test whether they are
+//! already formatted with [`check_str`]:
+//!
+//! ```
+//! use djade::{FormatOptions, Version, format_str};
+//!
+//! let mut options = FormatOptions::default();
+//! options.target_version = Some(Version::new(5, 2));
+//!
+//! let formatted = format_str("{%  trans 'Hello'  %}", &options).unwrap();
+//! assert_eq!(formatted, "{% translate 'Hello' %}\n");
+//! ```
+
+#[cfg(feature = "capi")]
+mod capi;
+#[cfg(not(target_arch = "wasm32"))]
+#[doc(hidden)]
+pub mod cli;
+mod jinja;
+#[cfg(feature = "python")]
+mod python;
+mod smartif;
+mod tree;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::LazyLock;
+use tree::{Node, Tree};
+
+// Public API
+
+/// A Django version, as `<major>.<minor>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl Version {
+    pub const fn new(major: u8, minor: u8) -> Self {
+        Self { major, minor }
+    }
+
+    pub fn as_tuple(&self) -> (u8, u8) {
+        (self.major, self.minor)
+    }
+
+    /// Whether this version is in [`SUPPORTED_TARGET_VERSIONS`].
+    pub fn is_supported(&self) -> bool {
+        SUPPORTED_TARGET_VERSIONS.contains(&self.to_string().as_str())
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for Version {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (major, minor) = s.split_once('.').ok_or(ParseVersionError)?;
+        Ok(Version::new(
+            major.parse().map_err(|_| ParseVersionError)?,
+            minor.parse().map_err(|_| ParseVersionError)?,
+        ))
+    }
+}
+
+/// The error from parsing a [`Version`] not formatted as `<major>.<minor>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError;
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("version must be formatted as <major>.<minor>")
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+/// The Django versions Djade knows about, oldest first. This list drives the
+/// `--target-version` choices, auto-detection, and target version
+/// validation.
+pub const SUPPORTED_TARGET_VERSIONS: &[&str] = &[
+    "2.1", "2.2", "3.0", "3.1", "3.2", "4.1", "4.2", "5.0", "5.1", "5.2", "6.0", "6.1",
+];
+
+/// How to write newlines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NewlinePolicy {
+    /// Keep existing newlines, and add new ones matching the template’s first
+    /// newline, or `\n` if it has none.
+    #[default]
+    Auto,
+    /// Use `\n` for all newlines.
+    Lf,
+    /// Use `\r\n` for all newlines.
+    CrLf,
+}
+
+/// Options for [`format_str`] and [`check_str`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct FormatOptions {
+    /// The Django version to target. Fixers for versions up to and including
+    /// it run, and `None` disables all version-dependent fixers.
+    pub target_version: Option<Version>,
+    pub newline: NewlinePolicy,
+    /// The fixers to run, if active for `target_version`. Defaults to
+    /// [`Fixer::DEFAULT`].
+    pub fixers: Vec<Fixer>,
+    /// Extra block tags closed by `end` + their name, like `component` for
+    /// `{% component %}` … `{% endcomponent %}`. Their end tags are labelled
+    /// like `{% endblock %}`.
+    pub paired_tags: Vec<String>,
+    /// The template language to parse. Jinja2 templates get only the
+    /// formatters in [`Dialect::formatters`], and no fixers.
+    pub dialect: Dialect,
+    /// The quote to rewrite string constants to use, or `None` to leave them
+    /// as they are.
+    pub quote_style: Option<QuoteStyle>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            target_version: None,
+            newline: NewlinePolicy::Auto,
+            fixers: Fixer::DEFAULT.to_vec(),
+            paired_tags: Vec::new(),
+            dialect: Dialect::Django,
+            quote_style: None,
+        }
+    }
+}
+
+/// A quote for string constants, like `"base.html"` or `'base.html'`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteStyle {
+    Double,
+    Single,
+}
+
+impl QuoteStyle {
+    /// The name used in configuration.
+    pub fn name(&self) -> &'static str {
+        match self {
+            QuoteStyle::Double => "double",
+            QuoteStyle::Single => "single",
+        }
+    }
+
+    fn quote(&self) -> char {
+        match self {
+            QuoteStyle::Double => '"',
+            QuoteStyle::Single => '\'',
+        }
+    }
+}
+
+/// The template language of a template.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dialect {
+    #[default]
+    Django,
+    Jinja2,
+}
+
+impl Dialect {
+    /// The file extensions of Jinja2 templates, without their dots.
+    pub const JINJA2_EXTENSIONS: &[&str] = &["jinja", "jinja2", "j2"];
+
+    /// The dialect of a template file, from its extension.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if Self::JINJA2_EXTENSIONS.contains(&extension) => Dialect::Jinja2,
+            _ => Dialect::Django,
+        }
+    }
+
+    /// The name used on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dialect::Django => "django",
+            Dialect::Jinja2 => "jinja2",
+        }
+    }
+
+    /// The formatters that run on templates of this dialect, in the order
+    /// they are applied.
+    pub fn formatters(&self) -> &'static [Formatter] {
+        match self {
+            Dialect::Django => &Formatter::ALL,
+            Dialect::Jinja2 => &[
+                Formatter::TagSpacing,
+                Formatter::LeadingTrailingWhitespace,
+                Formatter::EndblockLabels,
+            ],
+        }
+    }
+
+    /// The template regions left untouched in templates of this dialect.
+    pub fn exclusions(&self) -> &'static [&'static str] {
+        match self {
+            Dialect::Django => EXCLUSIONS,
+            Dialect::Jinja2 => JINJA2_EXCLUSIONS,
+        }
+    }
+}
+
+/// A position in a template, counting lines and characters from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// The errors Djade can produce. Embedders can branch on the kind, whilst
+/// the command line interface renders them with [`Display`](fmt::Display).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DjadeError {
+    /// Reading a template failed. `path` is `None` for standard input.
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// A template is not valid UTF-8.
+    Encoding { path: PathBuf },
+    /// A template has syntax that Django would reject.
+    Syntax { message: String, location: Location },
+    /// A configuration value is invalid.
+    Config { message: String },
+    /// The target version is not in [`SUPPORTED_TARGET_VERSIONS`].
+    UnsupportedVersion(Version),
+}
+
+impl DjadeError {
+    /// Convert an error from reading a template, with `None` for standard
+    /// input.
+    pub fn from_read(path: Option<&Path>, source: io::Error) -> Self {
+        match path {
+            Some(path) if source.kind() == io::ErrorKind::InvalidData => DjadeError::Encoding {
+                path: path.to_path_buf(),
+            },
+            _ => DjadeError::Io {
+                path: path.map(Path::to_path_buf),
+                source,
+            },
+        }
+    }
+}
+
+impl fmt::Display for DjadeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DjadeError::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "Error reading {}: {}", path.display(), source),
+            DjadeError::Io { path: None, source } => {
+                write!(f, "Error reading from stdin: {}", source)
+            }
+            DjadeError::Encoding { path } => {
+                write!(f, "{} is non-UTF-8 (not supported)", path.display())
+            }
+            DjadeError::Syntax { message, location } => write!(f, "{} at {}", message, location),
+            DjadeError::Config { message } => write!(f, "invalid configuration: {}", message),
+            DjadeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported target version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DjadeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DjadeError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A problem found in a template that doesn’t stop it being formatted, but
+/// that Django would reject.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    pub message: String,
+    pub location: Location,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.location)
+    }
+}
+
+/// Format a template’s contents.
+pub fn format_str(content: &str, options: &FormatOptions) -> Result<String, DjadeError> {
+    if let Some(version) = options.target_version
+        && !version.is_supported()
+    {
+        return Err(DjadeError::UnsupportedVersion(version));
+    }
+
+    format(content, options)
+}
+
+/// Return whether a template’s contents are already formatted.
+pub fn check_str(content: &str, options: &FormatOptions) -> Result<bool, DjadeError> {
+    Ok(format_str(content, options)? == content)
+}
+
+/// Find unexpected, mismatched, and unclosed block tags, unparseable
+/// variables, invalid if expressions, empty tags, and multi-line tags in a
+/// template’s contents, following Django’s parsing rules. Tags in
+/// `options.paired_tags` are treated as paired.
+pub fn diagnose_str(content: &str, options: &FormatOptions) -> Result<Vec<Diagnostic>, DjadeError> {
+    let tokens = lex(content);
+    let tree = Tree::parse_with_paired_tags(&tokens, &options.paired_tags);
+    let mut diagnostics = tree.diagnostics.clone();
+    for (i, token) in tokens.iter().enumerate() {
+        let message = match token {
+            Token::Text { contents, .. } if !in_verbatim(&tokens, &tree, i) => {
+                empty_tag_error(contents).map(str::to_string)
+            }
+            Token::Variable {
+                filter_expression:
+                    FilterExpression {
+                        var: Expression::Unparsed(expr),
+                        ..
+                    },
+                ..
+            } => filter_expression_error(expr),
+            Token::Block { bits, .. } if matches!(bits[0].as_ref(), "if" | "elif") => {
+                smartif::parse(&bits[1..]).err()
+            }
+            _ => None,
+        };
+        if let Some(message) = message {
+            diagnostics.push(Diagnostic {
+                message,
+                location: token.span().location,
+            });
+        }
+    }
+    for tag in find_multiline_tags(&tokens, &tree) {
+        diagnostics.push(Diagnostic {
+            message: if tag.closed {
+                "multi-line tag rendered as text"
+            } else {
+                "unclosed multi-line tag rendered as text"
+            }
+            .to_string(),
+            location: tag.location,
+        });
+    }
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.location.line, diagnostic.location.column));
+    Ok(diagnostics)
+}
+
+// Lexer based on Django’s:
+// https://github.com/django/django/blob/main/django/template/base.py
+// Hand-written scanners replace Django’s regular expressions, matching the
+// same text in a single pass.
+
+const BLOCK_TAG_START: &str = "{%";
+const VARIABLE_TAG_START: &str = "{{";
+const COMMENT_TAG_START: &str = "{#";
+
+/// Where a token came from in the template: its start and end byte
+/// offsets, and the location of its start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+struct Span {
+    start: usize,
+    end: usize,
+    #[serde(flatten)]
+    location: Location,
+}
+
+impl Span {
+    /// The location of a byte offset within the token’s text.
+    fn location_at(&self, text: &str, offset: usize) -> Location {
+        let before = &text[..offset];
+        match before.rfind('\n') {
+            Some(last_newline) => Location {
+                line: self.location.line + before.matches('\n').count(),
+                column: before[last_newline + 1..].chars().count() + 1,
+            },
+            None => Location {
+                line: self.location.line,
+                column: self.location.column + before.chars().count(),
+            },
+        }
+    }
+
+    /// An empty span at the given byte offset.
+    fn empty_at(template_string: &str, offset: usize) -> Span {
+        let before = &template_string[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Span {
+            start: offset,
+            end: offset,
+            location: Location {
+                line: before.matches('\n').count() + 1,
+                column: before[line_start..].chars().count() + 1,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Token<'a> {
+    Text {
+        contents: Cow<'a, str>,
+        span: Span,
+    },
+    Variable {
+        filter_expression: FilterExpression<'a>,
+        #[serde(skip_serializing_if = "Markers::is_empty")]
+        markers: Markers,
+        span: Span,
+    },
+    Block {
+        bits: Vec<Cow<'a, str>>,
+        #[serde(skip_serializing_if = "Markers::is_empty")]
+        markers: Markers,
+        span: Span,
+    },
+    Comment {
+        contents: Cow<'a, str>,
+        #[serde(skip_serializing_if = "Markers::is_empty")]
+        markers: Markers,
+        span: Span,
+    },
+}
+
+/// Jinja’s whitespace control markers, like the `-` in `{%-` and `-%}`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+struct Markers {
+    start: Option<char>,
+    end: Option<char>,
+}
+
+impl Markers {
+    fn is_empty(&self) -> bool {
+        self.start.is_none() && self.end.is_none()
+    }
+
+    fn push_start(&self, result: &mut String, tag_start: &str) {
+        result.push_str(tag_start);
+        result.extend(self.start);
+        result.push(' ');
+    }
+
+    fn push_end(&self, result: &mut String, tag_end: &str) {
+        result.push(' ');
+        result.extend(self.end);
+        result.push_str(tag_end);
+    }
+}
+
+impl Token<'_> {
+    fn span(&self) -> Span {
+        match self {
+            Token::Text { span, .. }
+            | Token::Variable { span, .. }
+            | Token::Block { span, .. }
+            | Token::Comment { span, .. } => *span,
+        }
+    }
+
+    /// The token’s type, as named in JSON output.
+    fn kind(&self) -> &'static str {
+        match self {
+            Token::Text { .. } => "text",
+            Token::Variable { .. } => "variable",
+            Token::Block { .. } => "block",
+            Token::Comment { .. } => "comment",
+        }
+    }
+
+    fn span_mut(&mut self) -> &mut Span {
+        match self {
+            Token::Text { span, .. }
+            | Token::Variable { span, .. }
+            | Token::Block { span, .. }
+            | Token::Comment { span, .. } => span,
+        }
+    }
+}
+
+/// Tracks the locations of consecutive tokens whilst lexing.
+struct SpanTracker {
+    lineno: usize,
+    line_start: usize,
+}
+
+impl SpanTracker {
+    fn new() -> Self {
+        Self {
+            lineno: 1,
+            line_start: 0,
+        }
+    }
+
+    /// The span of the token from `start` to `end`, which must follow the
+    /// previous token.
+    fn span(&mut self, template_string: &str, start: usize, end: usize) -> Span {
+        let span = Span {
+            start,
+            end,
+            location: Location {
+                line: self.lineno,
+                column: template_string[self.line_start..start].chars().count() + 1,
+            },
+        };
+        let token_string = &template_string[start..end];
+        if let Some(last_newline) = token_string.rfind('\n') {
+            self.lineno += token_string.matches('\n').count();
+            self.line_start = start + last_newline + 1;
+        }
+        span
+    }
+}
+
+fn lex<'a>(template_string: &'a str) -> Vec<Token<'a>> {
+    let mut result = Vec::new();
+    let mut verbatim = None;
+    let mut spans = SpanTracker::new();
+    let mut last_end = 0;
+
+    let mut push_token = |start: usize, end: usize, in_tag: bool| {
+        let token_string = &template_string[start..end];
+        let span = spans.span(template_string, start, end);
+        result.push(create_token(token_string, span, in_tag, &mut verbatim));
+    };
+
+    while let Some((start, end)) = next_tag(template_string, last_end) {
+        if start > last_end {
+            push_token(last_end, start, false);
+        }
+
+        push_token(start, end, true);
+
+        last_end = end;
+    }
+
+    if last_end < template_string.len() {
+        push_token(last_end, template_string.len(), false);
+    }
+
+    result
+}
+
+/// Find the start and end of the next tag at or after `from`, like Django’s
+/// TAG_RE: an opening `{%`, `{{`, or `{#` up to the first matching closer on
+/// the same line.
+fn next_tag(template_string: &str, mut from: usize) -> Option<(usize, usize)> {
+    while let Some(offset) = template_string[from..].find('{') {
+        let start = from + offset;
+        let closer = match template_string.as_bytes().get(start + 1) {
+            Some(b'%') => "%}",
+            Some(b'{') => "}}",
+            Some(b'#') => "#}",
+            _ => {
+                from = start + 1;
+                continue;
+            }
+        };
+        let rest = &template_string[start + 2..];
+        let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        if let Some(closer_start) = line.find(closer) {
+            return Some((start, start + 2 + closer_start + 2));
+        }
+        from = start + 1;
+    }
+    None
+}
+
+/// The error Django raises for an empty tag, like `{% %}` or `{{ }}`.
+fn empty_tag_error(token_string: &str) -> Option<&'static str> {
+    let (message, inner) = if let Some(rest) = token_string.strip_prefix(BLOCK_TAG_START) {
+        ("empty block tag", rest.strip_suffix("%}")?)
+    } else {
+        let rest = token_string.strip_prefix(VARIABLE_TAG_START)?;
+        ("empty variable tag", rest.strip_suffix("}}")?)
+    };
+    inner.trim().is_empty().then_some(message)
+}
+
+fn create_token<'a>(
+    token_string: &'a str,
+    span: Span,
+    in_tag: bool,
+    verbatim: &mut Option<String>,
+) -> Token<'a> {
+    if in_tag {
+        let content = token_string[2..token_string.len() - 2].trim();
+        if token_string.starts_with(BLOCK_TAG_START) {
+            if let Some(v) = &verbatim {
+                if content != v {
+                    return Token::Text {
+                        contents: Cow::Borrowed(token_string),
+                        span,
+                    };
+                }
+                *verbatim = None;
+            } else if content.is_empty() {
+                // Kept as text, like empty variable tags. diagnose_str()
+                // reports both.
+                return Token::Text {
+                    contents: Cow::Borrowed(token_string),
+                    span,
+                };
+            } else if content.starts_with("verbatim") {
+                *verbatim = Some(format!("end{}", content));
+            } else if content.split_whitespace().next() == Some("comment") {
+                // Django’s parser skips to the next {% endcomment %}.
+                *verbatim = Some("endcomment".to_string());
+            }
+            Token::Block {
+                bits: split_contents(content),
+                markers: Markers::default(),
+                span,
+            }
+        } else if verbatim.is_none() && empty_tag_error(token_string).is_none() {
+            if token_string.starts_with(VARIABLE_TAG_START) {
+                Token::Variable {
+                    filter_expression: lex_filter_expression(content),
+                    markers: Markers::default(),
+                    span,
+                }
+            } else {
+                debug_assert!(token_string.starts_with(COMMENT_TAG_START));
+                Token::Comment {
+                    contents: Cow::Borrowed(content),
+                    markers: Markers::default(),
+                    span,
+                }
+            }
+        } else {
+            Token::Text {
+                contents: Cow::Borrowed(token_string),
+                span,
+            }
+        }
+    } else {
+        Token::Text {
+            contents: Cow::Borrowed(token_string),
+            span,
+        }
+    }
+}
+
+// Expression lexer based on Django’s FilterExpression:
+// https://github.com/django/django/blob/ad7f8129f3d2de937611d72e257fb07d1306a855/django/template/base.py#L617
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+enum Expression<'a> {
+    Constant(Cow<'a, str>),
+    Variable(Cow<'a, str>),
+    Unparsed(Cow<'a, str>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct FilterExpression<'a> {
+    var: Expression<'a>,
+    filters: Vec<Filter<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Filter<'a> {
+    name: Cow<'a, str>,
+    arg: Option<Expression<'a>>,
+}
+
+/// A block tag argument: a filter expression, optionally passed as a keyword
+/// argument like `key=value`.
+#[derive(Debug, PartialEq)]
+struct TagArgument<'a> {
+    key: Option<&'a str>,
+    filter_expression: FilterExpression<'a>,
+}
+
+/// Tags whose arguments aren’t filter expressions.
+const NON_EXPRESSION_TAGS: &[&str] = &["comment", "load", "templatetag", "verbatim"];
+
+/// Lex a block tag bit as an argument, returning None if it isn’t a valid
+/// filter expression.
+fn lex_tag_argument(bit: &str) -> Option<TagArgument<'_>> {
+    // Like Django’s token_kwargs(), but not splitting `==`.
+    let key_end = skip_while(bit, 0, is_word_char);
+    let (key, value) = match bit[key_end..].strip_prefix('=') {
+        Some(value) if key_end > 0 && !value.starts_with('=') => (Some(&bit[..key_end]), value),
+        _ => (None, bit),
+    };
+    let filter_expression = scan_filter_expression(value).ok()?;
+    if matches!(filter_expression.var, Expression::Unparsed(_)) {
+        return None;
+    }
+    Some(TagArgument {
+        key,
+        filter_expression,
+    })
+}
+
+fn format_tag_argument(argument: TagArgument) -> String {
+    let mut result = String::new();
+    if let Some(key) = argument.key {
+        result.push_str(key);
+        result.push('=');
+    }
+    format_variable(argument.filter_expression, &mut result);
+    result
+}
+
+/// Apply `update` to the filter expressions of a block tag’s arguments,
+/// rewriting those it changes.
+fn update_tag_arguments(
+    bits: &mut [Cow<'_, str>],
+    mut update: impl FnMut(&mut FilterExpression<'_>) -> bool,
+) {
+    if NON_EXPRESSION_TAGS.contains(&bits[0].as_ref()) {
+        return;
+    }
+    for bit in &mut bits[1..] {
+        let Some(mut argument) = lex_tag_argument(bit) else {
+            continue;
+        };
+        if update(&mut argument.filter_expression) {
+            let formatted = format_tag_argument(argument);
+            *bit = Cow::Owned(formatted);
+        }
+    }
+}
+
+fn lex_filter_expression<'a>(expr: &'a str) -> FilterExpression<'a> {
+    // Syntax error - ignore it and return whole expression as constant
+    scan_filter_expression(expr).unwrap_or_else(|_| FilterExpression {
+        var: Expression::Unparsed(Cow::Borrowed(expr)),
+        filters: Vec::new(),
+    })
+}
+
+/// Scan a filter expression, returning the offset of the first character
+/// that can’t be parsed on failure.
+fn scan_filter_expression<'a>(expr: &'a str) -> Result<FilterExpression<'a>, usize> {
+    let (var, mut upto) = match scan_expression(expr, 0) {
+        Some(found) => found,
+        None => {
+            // A filter with nothing before it leaves the variable unparsed.
+            let (_, end) = scan_filter(expr, 0).ok_or(0usize)?;
+            (Expression::Unparsed(Cow::Borrowed(expr)), end)
+        }
+    };
+    let mut filters = Vec::new();
+    while upto < expr.len() {
+        let (filter, end) = scan_filter(expr, upto).ok_or(upto)?;
+        filters.push(filter);
+        upto = end;
+    }
+    // An unparsed variable already holds the filters.
+    if let Expression::Unparsed(_) = var {
+        filters.clear();
+    }
+    Ok(FilterExpression { var, filters })
+}
+
+/// Django’s error message for an invalid filter expression, or None if it’s
+/// valid.
+fn filter_expression_error(expr: &str) -> Option<String> {
+    match scan_filter_expression(expr) {
+        Ok(FilterExpression {
+            var: Expression::Unparsed(_),
+            ..
+        }) => Some(format!("could not find variable at start of '{}'", expr)),
+        Ok(_) => None,
+        Err(upto) => Some(format!(
+            "could not parse the remainder: '{}' from '{}'",
+            &expr[upto..],
+            expr
+        )),
+    }
+}
+
+/// Scan a constant or variable starting at `pos`, returning it and its end.
+fn scan_expression(expr: &str, pos: usize) -> Option<(Expression<'_>, usize)> {
+    if let Some(end) = scan_constant(expr, pos) {
+        return Some((Expression::Constant(Cow::Borrowed(&expr[pos..end])), end));
+    }
+    let end = skip_while(expr, pos, |c| {
+        is_word_char(c) || matches!(c, '.' | '+' | '-')
+    });
+    (end > pos).then(|| (Expression::Variable(Cow::Borrowed(&expr[pos..end])), end))
+}
+
+/// Scan a string constant starting at `pos`, optionally marked for
+/// translation with `_()`, returning its end.
+fn scan_constant(expr: &str, pos: usize) -> Option<usize> {
+    if expr[pos..].starts_with("_(") {
+        let end = scan_string(expr, pos + 2)?;
+        expr[end..].starts_with(')').then_some(end + 1)
+    } else {
+        scan_string(expr, pos)
+    }
+}
+
+/// Scan a filter starting at `pos`, with its surrounding whitespace and
+/// optional argument, returning it and its end.
+fn scan_filter(expr: &str, pos: usize) -> Option<(Filter<'_>, usize)> {
+    let pos = skip_while(expr, pos, char::is_whitespace);
+    if !expr[pos..].starts_with('|') {
+        return None;
+    }
+    let name_start = skip_while(expr, pos + 1, char::is_whitespace);
+    let name_end = skip_while(expr, name_start, is_word_char);
+    if name_end == name_start {
+        return None;
+    }
+    let name = Cow::Borrowed(&expr[name_start..name_end]);
+    if expr[name_end..].starts_with(':')
+        && let Some((arg, end)) = scan_expression(expr, name_end + 1)
+    {
+        return Some((
+            Filter {
+                name,
+                arg: Some(arg),
+            },
+            end,
+        ));
+    }
+    Some((Filter { name, arg: None }, name_end))
+}
+
+/// Scan a single- or double-quoted string starting at `pos`, with backslash
+/// escapes of anything but a newline, returning its end.
+fn scan_string(text: &str, pos: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let quote = *bytes.get(pos).filter(|&&b| b == b'"' || b == b'\'')?;
+    let mut i = pos + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b if b == quote => return Some(i + 1),
+            b'\\' if matches!(bytes.get(i + 1), None | Some(b'\n')) => return None,
+            b'\\' => i += 2,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Return the offset of the first character at or after `pos` not matching
+/// `predicate`.
+fn skip_while(text: &str, pos: usize, predicate: impl Fn(char) -> bool) -> usize {
+    text[pos..]
+        .find(|c| !predicate(c))
+        .map_or(text.len(), |offset| pos + offset)
+}
+
+/// Whether a character matches `\w`, with Unicode support like Python’s re.
+fn is_word_char(c: char) -> bool {
+    if c.is_ascii() {
+        c.is_ascii_alphanumeric() || c == '_'
+    } else {
+        regex_syntax::is_word_character(c)
+    }
+}
+
+/// Split on whitespace, keeping quoted strings together, like Django’s
+/// smart_split().
+fn smart_split(text: &str) -> impl Iterator<Item = &str> {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let start = skip_while(text, pos, char::is_whitespace);
+        if start == text.len() {
+            return None;
+        }
+        // Unquoted text joined with complete quoted strings, else any
+        // non-whitespace.
+        let is_unquoted = |c: char| !c.is_whitespace() && c != '"' && c != '\'';
+        let mut end = None;
+        let mut upto = skip_while(text, start, is_unquoted);
+        while let Some(string_end) = scan_string(text, upto) {
+            upto = skip_while(text, string_end, is_unquoted);
+            end = Some(upto);
+        }
+        pos = end.unwrap_or_else(|| skip_while(text, start, |c| !c.is_whitespace()));
+        Some(&text[start..pos])
+    })
+}
+
+fn split_contents<'a>(contents: &'a str) -> Vec<Cow<'a, str>> {
+    let mut split = Vec::new();
+    let mut bits = smart_split(contents);
+
+    while let Some(bit) = bits.next() {
+        let mut chars = bit.chars();
+        if matches!(chars.next(), Some('_'))
+            && matches!(chars.next(), Some('('))
+            && matches!(chars.next(), Some('"' | '\''))
+        {
+            let sentinel = format!("{})", &bit[2..3]);
+            let mut trans_bit = vec![bit];
+            while !trans_bit.last().unwrap().ends_with(&sentinel) {
+                if let Some(next_bit) = bits.next() {
+                    trans_bit.push(next_bit);
+                } else {
+                    break;
+                }
+            }
+            split.push(Cow::Owned(trans_bit.join(" ")));
+        } else {
+            split.push(Cow::Borrowed(bit));
+        }
+    }
+    split
+}
+
+fn format(content: &str, options: &FormatOptions) -> Result<String, DjadeError> {
+    let django = options.dialect == Dialect::Django;
+    let rewritten;
+    let content = if !django {
+        content
+    } else if let Some(result) = fix_multiline_tags(content, options) {
+        rewritten = result;
+        &rewritten
+    } else {
+        content
+    };
+
+    // Lex
+    let newline = match options.newline {
+        NewlinePolicy::Auto => detect_newline(content),
+        NewlinePolicy::Lf => "\n",
+        NewlinePolicy::CrLf => "\r\n",
+    };
+    let mut tokens = match options.dialect {
+        Dialect::Django => lex(content),
+        Dialect::Jinja2 => jinja::lex(content)?,
+    };
+
+    // Fixers
+    if django {
+        for fixer in Fixer::ALL {
+            if options.fixers.contains(&fixer) && fixer.is_active(options.target_version) {
+                fixer.apply(&mut tokens, &options.paired_tags);
+            }
+        }
+    }
+
+    // Formatters
+    let context = FormatContext {
+        options,
+        newline,
+        end_of_file: Span::empty_at(content, content.len()),
+    };
+    for formatter in options.dialect.formatters() {
+        if formatter.is_enabled(options) {
+            formatter.apply(&mut tokens, &context);
+        }
+    }
+
+    // Final build
+    let mut result = String::new();
+    for token in tokens {
+        match token {
+            Token::Text { contents, .. } => result.push_str(&contents),
+            Token::Variable {
+                filter_expression,
+                markers,
+                ..
+            } => {
+                markers.push_start(&mut result, VARIABLE_TAG_START);
+                format_variable(filter_expression, &mut result);
+                markers.push_end(&mut result, "}}");
+            }
+            Token::Block { bits, markers, .. } => {
+                markers.push_start(&mut result, BLOCK_TAG_START);
+                result.push_str(&bits.join(" "));
+                markers.push_end(&mut result, "%}");
+            }
+            Token::Comment {
+                contents, markers, ..
+            } => {
+                markers.push_start(&mut result, COMMENT_TAG_START);
+                result.push_str(&contents);
+                markers.push_end(&mut result, "#}");
+            }
+        }
+    }
+
+    if options.newline != NewlinePolicy::Auto {
+        result = normalize_newlines(result, newline);
+    }
+    Ok(result)
+}
+
+fn normalize_newlines(content: String, newline: &str) -> String {
+    let has_crlf = content.contains("\r\n");
+    match newline {
+        "\n" if has_crlf => content.replace("\r\n", "\n"),
+        "\r\n" if has_crlf => content.replace("\r\n", "\n").replace('\n', "\r\n"),
+        "\r\n" if content.contains('\n') => content.replace('\n', "\r\n"),
+        _ => content,
+    }
+}
+
+fn detect_newline(content: &str) -> &str {
+    match content.split_once('\n') {
+        Some((s, _)) if s.ends_with('\r') => "\r\n",
+        _ => "\n",
+    }
+}
+
+#[inline(always)]
+fn format_variable(filter_expression: FilterExpression, result: &mut String) {
+    match filter_expression.var {
+        Expression::Constant(value) | Expression::Variable(value) | Expression::Unparsed(value) => {
+            result.push_str(&value);
+        }
+    }
+    for filter in filter_expression.filters {
+        result.push('|');
+        result.push_str(&filter.name);
+        if let Some(arg) = filter.arg {
+            result.push(':');
+            match arg {
+                Expression::Constant(value)
+                | Expression::Variable(value)
+                | Expression::Unparsed(value) => {
+                    result.push_str(&value);
+                }
+            }
+        }
+    }
+}
+
+// Multi-line tags
+
+// Like Django’s TAG_RE, but with . matching newlines. A tag with no closer
+// runs to the end of the text.
+static MULTILINE_TAG_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)\{%.*?(?:%\}|\z)|\{\{.*?(?:\}\}|\z)|\{#.*?(?:#\}|\z)").unwrap()
+});
+
+static NEWLINE_WHITESPACE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s*\n\s*").unwrap());
+
+/// A tag split over multiple lines, which Django’s lexer leaves as text.
+struct MultilineTag {
+    start: usize,
+    end: usize,
+    location: Location,
+    /// Whether the tag has a closer, rather than running to the next tag or
+    /// the end of the template.
+    closed: bool,
+}
+
+/// Whether the token at `i` is inside a verbatim or comment block, whose
+/// contents Django doesn’t parse.
+fn in_verbatim(tokens: &[Token<'_>], tree: &Tree, i: usize) -> bool {
+    if let Some(parent) = tree.positions[i].parent
+        && let Token::Block { bits, .. } = &tokens[parent]
+    {
+        matches!(bits[0].as_ref(), "verbatim" | "comment")
+    } else {
+        false
+    }
+}
+
+fn find_multiline_tags(tokens: &[Token<'_>], tree: &Tree) -> Vec<MultilineTag> {
+    let mut tags = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::Text { contents, span } = token else {
+            continue;
+        };
+        if in_verbatim(tokens, tree, i) {
+            continue;
+        }
+        for tag in MULTILINE_TAG_RE.find_iter(contents) {
+            let source = tag.as_str();
+            let closer = match &source[..2] {
+                BLOCK_TAG_START => "%}",
+                VARIABLE_TAG_START => "}}",
+                _ => "#}",
+            };
+            if source.contains('\n') {
+                tags.push(MultilineTag {
+                    start: span.start + tag.start(),
+                    end: span.start + tag.end(),
+                    location: span.location_at(contents, tag.start()),
+                    closed: source.len() >= 4 && source.ends_with(closer),
+                });
+            }
+        }
+    }
+    tags
+}
+
+static ENDCOMMENT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{%\s*endcomment\s*%\}").unwrap());
+
+/// Apply the multi-line tag fixers in `options.fixers` to the template
+/// source, returning None if nothing changed.
+fn fix_multiline_tags(content: &str, options: &FormatOptions) -> Option<String> {
+    let convert_comments = options.fixers.contains(&Fixer::MultilineComments);
+    let join_tags = options.fixers.contains(&Fixer::MultilineTags);
+    if !convert_comments && !join_tags {
+        return None;
+    }
+    let tokens = lex(content);
+    let tree = Tree::parse_with_paired_tags(&tokens, &options.paired_tags);
+    let tags = find_multiline_tags(&tokens, &tree);
+
+    let mut result = String::with_capacity(content.len());
+    let mut last_end = 0;
+    for tag in tags.into_iter().filter(|tag| tag.closed) {
+        let source = &content[tag.start..tag.end];
+        let inner = &source[2..source.len() - 2];
+        let replacement = if convert_comments
+            && source.starts_with(COMMENT_TAG_START)
+            && !ENDCOMMENT_RE.is_match(inner)
+        {
+            Cow::Owned(format!("{{% comment %}}{}{{% endcomment %}}", inner))
+        } else if join_tags {
+            NEWLINE_WHITESPACE_RE.replace_all(source, " ")
+        } else {
+            continue;
+        };
+        result.push_str(&content[last_end..tag.start]);
+        result.push_str(&replacement);
+        last_end = tag.end;
+    }
+    if last_end == 0 {
+        return None;
+    }
+    result.push_str(&content[last_end..]);
+    Some(result)
+}
+
+// Fixers
+
+/// The fixers that upgrade old template syntax or repair mistakes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Fixer {
+    MultilineComments,
+    MultilineTags,
+    LengthIs,
+    EmptyJsonScript,
+    TranslationTags,
+    IfequalTags,
+    StaticLoadTags,
+    Assignments,
+}
+
+impl Fixer {
+    /// All fixers, in the order they are applied.
+    pub const ALL: [Fixer; 8] = [
+        Fixer::MultilineComments,
+        Fixer::MultilineTags,
+        Fixer::LengthIs,
+        Fixer::EmptyJsonScript,
+        Fixer::TranslationTags,
+        Fixer::IfequalTags,
+        Fixer::StaticLoadTags,
+        Fixer::Assignments,
+    ];
+
+    /// The fixers that run unless configured otherwise: all except the
+    /// opt-in ones.
+    pub const DEFAULT: [Fixer; 6] = [
+        Fixer::LengthIs,
+        Fixer::EmptyJsonScript,
+        Fixer::TranslationTags,
+        Fixer::IfequalTags,
+        Fixer::StaticLoadTags,
+        Fixer::Assignments,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Fixer::MultilineComments => "multiline-comments",
+            Fixer::MultilineTags => "multiline-tags",
+            Fixer::LengthIs => "length-is",
+            Fixer::EmptyJsonScript => "empty-json-script",
+            Fixer::TranslationTags => "translation-tags",
+            Fixer::IfequalTags => "ifequal-tags",
+            Fixer::StaticLoadTags => "static-load-tags",
+            Fixer::Assignments => "assignments",
+        }
+    }
+
+    /// The target Django version the fixer needs, or None if it always
+    /// applies.
+    pub fn min_version(self) -> Option<Version> {
+        match self {
+            Fixer::LengthIs => Some(Version::new(4, 2)),
+            Fixer::EmptyJsonScript => Some(Version::new(4, 1)),
+            Fixer::TranslationTags | Fixer::IfequalTags => Some(Version::new(3, 1)),
+            Fixer::StaticLoadTags => Some(Version::new(2, 1)),
+            Fixer::MultilineComments | Fixer::MultilineTags | Fixer::Assignments => None,
+        }
+    }
+
+    /// Whether the fixer changes what a template renders, so only runs
+    /// when enabled.
+    pub fn is_opt_in(self) -> bool {
+        !Fixer::DEFAULT.contains(&self)
+    }
+
+    pub fn is_active(self, target_version: Option<Version>) -> bool {
+        match (self.min_version(), target_version) {
+            (None, _) => true,
+            (Some(min_version), Some(target_version)) => target_version >= min_version,
+            (Some(_), None) => false,
+        }
+    }
+
+    fn apply(self, tokens: &mut [Token<'_>], paired_tags: &[String]) {
+        match self {
+            // Applied to the template source before lexing, in format().
+            Fixer::MultilineComments | Fixer::MultilineTags => {}
+            Fixer::LengthIs => migrate_length_is(tokens),
+            Fixer::EmptyJsonScript => migrate_empty_json_script(tokens),
+            Fixer::TranslationTags => migrate_translation_tags(tokens),
+            Fixer::IfequalTags => migrate_ifequal_tags(tokens, paired_tags),
+            Fixer::StaticLoadTags => migrate_static_load_tags(tokens),
+            Fixer::Assignments => migrate_assignments(tokens),
+        }
+    }
+}
+
+fn migrate_length_is(tokens: &mut [Token<'_>]) {
+    for token in tokens.iter_mut() {
+        let Token::Block { bits, .. } = token else {
+            continue;
+        };
+        if !matches!(bits[0].as_ref(), "if" | "elif") {
+            continue;
+        }
+        let Ok(mut expr) = smartif::parse(&smartif::split_operators(&bits[1..])) else {
+            continue;
+        };
+        // Only conditions, as `a|length == n` can’t be compared further.
+        let replaced = expr.replace_conditions(&mut |condition| {
+            let mut argument = lex_tag_argument(condition).filter(|a| a.key.is_none())?;
+            let filter = argument.filter_expression.filters.last_mut()?;
+            let Some(Expression::Variable(length)) = &filter.arg else {
+                return None;
+            };
+            if filter.name != "length_is" {
+                return None;
+            }
+            let length = length.to_string();
+            filter.name = Cow::Borrowed("length");
+            filter.arg = None;
+            Some(smartif::Expr::Infix {
+                operator: "==",
+                left: Box::new(smartif::Expr::Literal(Cow::Owned(format_tag_argument(
+                    argument,
+                )))),
+                right: Box::new(smartif::Expr::Literal(Cow::Owned(length))),
+            })
+        });
+        if replaced {
+            let mut new_bits = vec![bits[0].clone()];
+            new_bits.extend(expr.bits());
+            *bits = new_bits;
+        }
+    }
+}
+
+fn migrate_empty_json_script(tokens: &mut [Token<'_>]) {
+    for token in tokens.iter_mut() {
+        match token {
+            Token::Variable {
+                filter_expression, ..
+            } => {
+                remove_empty_json_script_ids(filter_expression);
+            }
+            Token::Block { bits, .. } => update_tag_arguments(bits, remove_empty_json_script_ids),
+            _ => {}
+        }
+    }
+}
+
+fn remove_empty_json_script_ids(filter_expression: &mut FilterExpression<'_>) -> bool {
+    let mut changed = false;
+    for filter in &mut filter_expression.filters {
+        if filter.name == "json_script"
+            && let Some(Expression::Constant(arg)) = &filter.arg
+            && (arg == "\"\"" || arg == "''")
+        {
+            filter.arg = None;
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn migrate_translation_tags(tokens: &mut [Token<'_>]) {
+    for token in tokens.iter_mut() {
+        if let Token::Block { bits, .. } = token {
+            match bits[0].as_ref() {
+                "trans" => {
+                    bits[0] = Cow::Borrowed("translate");
+                }
+                "blocktrans" => {
+                    bits[0] = Cow::Borrowed("blocktranslate");
+                }
+                "endblocktrans" => {
+                    bits[0] = Cow::Borrowed("endblocktranslate");
+                }
+                "load"
+                    if bits.len() >= 4
+                        && bits[bits.len() - 2] == "from"
+                        && bits[bits.len() - 1] == "i18n" =>
+                {
+                    for i in 1..bits.len() - 2 {
+                        if bits[i] == "trans" {
+                            bits[i] = Cow::Borrowed("translate");
+                        } else if bits[i] == "blocktrans" {
+                            bits[i] = Cow::Borrowed("blocktranslate");
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn migrate_ifequal_tags(tokens: &mut [Token<'_>], paired_tags: &[String]) {
+    let tree = Tree::parse_with_paired_tags(tokens, paired_tags);
+    for block in tree.blocks() {
+        let (start, Some(end)) = (block.start(), block.end) else {
+            continue;
+        };
+        let (
+            Token::Block {
+                bits: start_bits, ..
+            },
+            Token::Block { bits: end_bits, .. },
+        ) = (&tokens[start], &tokens[end])
+        else {
+            continue;
+        };
+        if !matches!(start_bits[0].as_ref(), "ifequal" | "ifnotequal")
+            || start_bits.len() != 3
+            || end_bits.len() != 1
+        {
+            continue;
+        }
+
+        let comparison = if start_bits[0] == "ifequal" {
+            "=="
+        } else {
+            "!="
+        };
+        let var1 = start_bits[1].clone();
+        let var2 = start_bits[2].clone();
+
+        // Update start token
+        if let Token::Block { bits, .. } = &mut tokens[start] {
+            bits.clear();
+            bits.push(Cow::Borrowed("if"));
+            bits.push(var1);
+            bits.push(Cow::Borrowed(comparison));
+            bits.push(var2);
+        }
+
+        // Update end token
+        if let Token::Block { bits, .. } = &mut tokens[end] {
+            bits.clear();
+            bits.push(Cow::Borrowed("endif"));
+        }
+    }
+}
+
+fn migrate_static_load_tags(tokens: &mut [Token<'_>]) {
+    for token in tokens.iter_mut() {
+        if let Token::Block { bits, .. } = token
+            && bits[0] == "load"
+        {
+            if bits.iter().any(|b| b == "from") {
+                if bits.len() >= 4 && bits[bits.len() - 2] == "from" {
+                    let last = bits.len() - 1;
+                    let library = bits[last].as_ref();
+                    if library == "admin_static" || library == "staticfiles" {
+                        bits[last] = Cow::Borrowed("static");
+                    }
+                }
+            } else {
+                for bit in bits.iter_mut().skip(1) {
+                    if bit == "admin_static" || bit == "staticfiles" {
+                        *bit = Cow::Borrowed("static");
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn migrate_assignments(tokens: &mut [Token<'_>]) {
+    for token in tokens.iter_mut() {
+        if let Token::Block { bits, .. } = token
+            && bits.iter().any(|b| b == "as")
+        {
+            match bits[0].as_ref() {
+                "with" => migrate_assignments_with_tag(bits),
+                "blocktrans" | "blocktranslate" => migrate_assignments_blocktranslate_tag(bits),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn migrate_assignments_with_tag(bits: &mut Vec<Cow<'_, str>>) {
+    let mut new_bits = vec![Cow::Borrowed("with")];
+    let mut i = 1;
+    while i < bits.len() {
+        if i + 2 < bits.len() && bits[i + 1] == "as" {
+            // Legacy format: "value as key"
+            new_bits.push(Cow::Owned(format!("{}={}", bits[i + 2], bits[i])));
+            i += 3;
+        } else if bits[i].contains('=') {
+            // Modern format: "key=value"
+            new_bits.push(bits[i].clone());
+            i += 1;
+        } else {
+            // Not a keyword argument, stop processing
+            new_bits.extend(bits[i..].iter().cloned());
+            break;
+        }
+
+        // Check for "and" between arguments
+        if i < bits.len() && bits[i] == "and" {
+            i += 1;
+        }
+    }
+    *bits = new_bits;
+}
+
+fn migrate_assignments_blocktranslate_tag(bits: &mut Vec<Cow<'_, str>>) {
+    let mut new_bits = vec![bits[0].clone()];
+    let mut i = 1;
+    let mut found_with = false;
+    let mut found_count = false;
+    while i < bits.len() {
+        match bits[i].as_ref() {
+            "with" if !found_with => {
+                found_with = true;
+                new_bits.push(Cow::Borrowed("with"));
+                i += 1;
+                while i < bits.len() {
+                    if i + 2 < bits.len() && bits[i + 1] == "as" {
+                        // Legacy format: "value as key"
+                        new_bits.push(Cow::Owned(format!("{}={}", bits[i + 2], bits[i])));
+                        i += 3;
+                    } else if bits[i].contains('=') {
+                        // Modern format: "key=value"
+                        new_bits.push(bits[i].clone());
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                    // Check for "and" between arguments
+                    if i < bits.len() && bits[i] == "and" {
+                        i += 1;
+                    }
+                }
+            }
+            "count" if !found_count => {
+                found_count = true;
+                new_bits.push(Cow::Borrowed("count"));
+                i += 1;
+                if i < bits.len() {
+                    if i + 2 < bits.len() && bits[i + 1] == "as" {
+                        // Legacy format: "value as count"
+                        new_bits.push(Cow::Owned(format!("{}={}", bits[i + 2], bits[i])));
+                        i += 2;
+                    } else if bits[i].contains('=') {
+                        // Modern format: "key=value"
+                        new_bits.push(bits[i].clone());
+                    }
+                }
+                i += 1;
+            }
+            _ => {
+                new_bits.push(bits[i].clone());
+                i += 1;
+            }
+        }
+    }
+    *bits = new_bits;
+}
+
+// Formatters
+
+/// The formatters that normalize a template’s layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Formatter {
+    TagSpacing,
+    LeadingTrailingWhitespace,
+    LoadTags,
+    IfExpressions,
+    QuoteStyle,
+    EndblockLabels,
+    TopLevelBlockIndentation,
+    TopLevelBlockSpacing,
+}
+
+/// What formatters need to know besides the tokens.
+struct FormatContext<'o> {
+    options: &'o FormatOptions,
+    newline: &'o str,
+    end_of_file: Span,
+}
+
+impl FormatContext<'_> {
+    /// Parse the paired tags of the template’s dialect.
+    fn parse_tree(&self, tokens: &[Token<'_>]) -> Tree {
+        let paired_tags = &self.options.paired_tags;
+        match self.options.dialect {
+            Dialect::Django => Tree::parse_with_paired_tags(tokens, paired_tags),
+            Dialect::Jinja2 => Tree::parse_jinja2(tokens, paired_tags),
+        }
+    }
+}
+
+impl Formatter {
+    /// All formatters, in the order they are applied.
+    pub const ALL: [Formatter; 8] = [
+        Formatter::TagSpacing,
+        Formatter::LeadingTrailingWhitespace,
+        Formatter::LoadTags,
+        Formatter::IfExpressions,
+        Formatter::QuoteStyle,
+        Formatter::EndblockLabels,
+        Formatter::TopLevelBlockIndentation,
+        Formatter::TopLevelBlockSpacing,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Formatter::TagSpacing => "tag-spacing",
+            Formatter::LeadingTrailingWhitespace => "leading-trailing-whitespace",
+            Formatter::LoadTags => "load-tags",
+            Formatter::IfExpressions => "if-expressions",
+            Formatter::QuoteStyle => "quote-style",
+            Formatter::EndblockLabels => "endblock-labels",
+            Formatter::TopLevelBlockIndentation => "top-level-block-indentation",
+            Formatter::TopLevelBlockSpacing => "top-level-block-spacing",
+        }
+    }
+
+    /// Whether the formatter runs with the given options, since some only
+    /// run when configured.
+    pub fn is_enabled(self, options: &FormatOptions) -> bool {
+        match self {
+            Formatter::QuoteStyle => options.quote_style.is_some(),
+            _ => true,
+        }
+    }
+
+    fn apply(self, tokens: &mut Vec<Token<'_>>, context: &FormatContext) {
+        let paired_tags = &context.options.paired_tags;
+        match self {
+            // Applied by the final build, which writes tags with single
+            // spaces.
+            Formatter::TagSpacing => {}
+            Formatter::LeadingTrailingWhitespace => {
+                update_leading_trailing_whitespace(tokens, context.newline, context.end_of_file)
+            }
+            Formatter::LoadTags => update_load_tags(tokens),
+            Formatter::IfExpressions => update_if_expressions(tokens),
+            Formatter::QuoteStyle => {
+                if let Some(quote_style) = context.options.quote_style {
+                    update_quote_style(tokens, quote_style);
+                }
+            }
+            Formatter::EndblockLabels => {
+                let tree = context.parse_tree(tokens);
+                update_endblock_and_endpartialdef_labels(tokens, &tree, paired_tags);
+            }
+            Formatter::TopLevelBlockIndentation => {
+                let tree = context.parse_tree(tokens);
+                update_top_level_block_indentation(tokens, &tree);
+            }
+            Formatter::TopLevelBlockSpacing => {
+                let tree = context.parse_tree(tokens);
+                update_top_level_block_spacing(tokens, &tree, context.newline);
+            }
+        }
+    }
+}
+
+/// Template regions that Djade leaves untouched, for display.
+pub const EXCLUSIONS: &[&str] = &[
+    "{% verbatim %} ... {% endverbatim %} contents",
+    "{% comment %} ... {% endcomment %} contents",
+];
+
+/// Jinja2 template regions that Djade leaves untouched, for display.
+pub const JINJA2_EXCLUSIONS: &[&str] = &[
+    "{% raw %} ... {% endraw %} contents",
+    "{{ ... }} and {# ... #} tags with multi-line contents",
+];
+
+fn update_leading_trailing_whitespace<'a>(
+    tokens: &mut Vec<Token<'a>>,
+    newline: &str,
+    end_of_file: Span,
+) {
+    if let Some(Token::Text { contents, .. }) = tokens.first_mut() {
+        let s = contents.as_ref();
+        let mut pos = 0;
+
+        loop {
+            let start = pos;
+            let chars = s[pos..].chars();
+
+            for c in chars {
+                if matches!(c, ' ' | '\t' | '\r') {
+                    pos += c.len_utf8();
+                } else {
+                    break;
+                }
+            }
+
+            if s[pos..].starts_with('\n') {
+                pos += 1;
+            } else {
+                pos = start;
+                break;
+            }
+        }
+
+        if pos > 0 {
+            *contents = Cow::Owned(s[pos..].to_string());
+        }
+    }
+
+    if let Some(mut token) = tokens.last_mut() {
+        if let Token::Text { contents, .. } = &mut token {
+            let s = contents.as_ref();
+            let mut end = s.len();
+
+            for (idx, c) in s.char_indices().rev() {
+                if matches!(c, ' ' | '\t' | '\r' | '\n') {
+                    end = idx;
+                } else {
+                    break;
+                }
+            }
+
+            let trimmed = &s[..end];
+            let remainder = &s[end..];
+            let already_correct = end == s.len() && newline.is_empty() || remainder == newline;
+
+            if !already_correct {
+                *contents = Cow::Owned(format!("{}{}", trimmed, newline));
+            }
+        } else {
+            tokens.push(Token::Text {
+                contents: Cow::Owned(newline.to_string()),
+                span: end_of_file,
+            });
+        }
+    }
+}
+
+/// Split comparison operators glued to their operands in if expressions, like
+/// `a==b`, if the result is valid. Django rejects glued operators, so
+/// diagnose_str() reports them.
+fn update_if_expressions<'a>(tokens: &mut [Token<'a>]) {
+    for token in tokens {
+        let Token::Block { bits, .. } = token else {
+            continue;
+        };
+        if !matches!(bits[0].as_ref(), "if" | "elif") {
+            continue;
+        }
+        let split = smartif::split_operators(&bits[1..]);
+        if split.len() == bits.len() - 1 {
+            continue;
+        }
+        let Ok(expr) = smartif::parse(&split) else {
+            continue;
+        };
+        let mut new_bits = vec![bits[0].clone()];
+        new_bits.extend(expr.bits());
+        *bits = new_bits;
+    }
+}
+
+/// Rewrite string constants in variables and tag arguments to use the
+/// configured quote.
+fn update_quote_style(tokens: &mut [Token<'_>], quote_style: QuoteStyle) {
+    let quote = quote_style.quote();
+    for token in tokens {
+        match token {
+            Token::Variable {
+                filter_expression, ..
+            } => {
+                requote_filter_expression(filter_expression, quote);
+            }
+            // A stray quote pairs with the next rewritten one when the tag
+            // is split again, so leave such tags alone.
+            Token::Block { bits, .. } if bits.iter().all(|bit| has_balanced_quotes(bit)) => {
+                update_tag_arguments(bits, |filter_expression| {
+                    requote_filter_expression(filter_expression, quote)
+                })
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether every string in a bit is closed.
+fn has_balanced_quotes(bit: &str) -> bool {
+    let mut open = None;
+    let mut chars = bit.chars();
+    while let Some(c) = chars.next() {
+        match (open, c) {
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(quote), _) if c == quote => open = None,
+            (None, '"' | '\'') => open = Some(c),
+            _ => {}
+        }
+    }
+    open.is_none()
+}
+
+fn requote_filter_expression(filter_expression: &mut FilterExpression<'_>, quote: char) -> bool {
+    let mut changed = requote_expression(&mut filter_expression.var, quote);
+    for filter in &mut filter_expression.filters {
+        if let Some(arg) = &mut filter.arg {
+            changed |= requote_expression(arg, quote);
+        }
+    }
+    changed
+}
+
+fn requote_expression(expression: &mut Expression<'_>, quote: char) -> bool {
+    if let Expression::Constant(value) = expression
+        && let Some(requoted) = requote_constant(value, quote)
+    {
+        *value = Cow::Owned(requoted);
+        return true;
+    }
+    false
+}
+
+/// Rewrite a string constant, which may be translated like `_("…")`, to use
+/// `quote`, dropping the escapes its old quote needed. Strings containing
+/// `quote` are left alone, since they would need new escapes, which Django
+/// doesn’t always remove.
+fn requote_constant(value: &str, quote: char) -> Option<String> {
+    let (prefix, literal, suffix) = match value.strip_prefix("_(") {
+        Some(inner) => ("_(", inner.strip_suffix(')')?, ")"),
+        None => ("", value, ""),
+    };
+    let source = literal.chars().next()?;
+    if source == quote {
+        return None;
+    }
+    let contents = &literal[1..literal.len() - 1];
+    if contents.contains(quote) {
+        return None;
+    }
+
+    let mut result = String::with_capacity(value.len());
+    result.push_str(prefix);
+    result.push(quote);
+    let mut chars = contents.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(escaped) if escaped == source => result.push(source),
+            Some(escaped) => {
+                result.push('\\');
+                result.push(escaped);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result.push(quote);
+    result.push_str(suffix);
+    Some(result)
+}
+
+fn update_load_tags<'a>(tokens: &mut Vec<Token<'a>>) {
+    let mut i = 0;
+    while i < tokens.len() {
+        if let Token::Block { ref bits, .. } = tokens[i]
+            && bits[0] == "load"
+        {
+            // load ... from ...
+            if bits.iter().any(|b| b == "from") {
+                if bits.len() >= 4 && bits[bits.len() - 2] == "from" {
+                    let library = bits[bits.len() - 1].clone();
+                    let mut parts = bits[1..bits.len() - 2].to_vec();
+
+                    parts.sort_unstable();
+                    parts.dedup();
+                    parts.insert(0, Cow::Borrowed("load"));
+                    parts.push(Cow::Borrowed("from"));
+                    parts.push(library);
+
+                    if let Token::Block { bits, .. } = &mut tokens[i] {
+                        bits.clear();
+                        bits.extend(parts);
+                    }
+                }
+            // load ...
+            } else {
+                let mut j = i + 1;
+                let mut to_merge = vec![i];
+                while j < tokens.len() {
+                    match &tokens[j] {
+                        Token::Text { contents, .. } if contents.trim().is_empty() => j += 1,
+                        Token::Block { bits, .. } if bits[0] == "load" => {
+                            if bits.iter().any(|b| b == "from") {
+                                break;
+                            }
+                            to_merge.push(j);
+                            j += 1
+                        }
+                        _ => break,
+                    }
+                }
+                if j > 0
+                    && let Token::Text { .. } = tokens[j - 1]
+                {
+                    j -= 1;
+                }
+
+                let mut parts: Vec<Cow<'_, str>> = to_merge
+                    .iter()
+                    .filter_map(|&idx| {
+                        if let Token::Block { bits, .. } = &tokens[idx] {
+                            Some(bits.iter().skip(1).cloned().collect::<Vec<_>>())
+                        } else {
+                            None
+                        }
+                    })
+                    .flatten()
+                    .collect();
+
+                parts.sort_unstable();
+                parts.dedup();
+                parts.insert(0, Cow::Borrowed("load"));
+
+                if let Token::Block { bits, .. } = &mut tokens[i] {
+                    bits.clear();
+                    bits.extend(parts);
+                }
+
+                if j > i + 1 {
+                    let end = tokens[j - 1].span().end;
+                    tokens[i].span_mut().end = end;
+                }
+                tokens.drain(i + 1..j);
+            }
+        }
+
+        i += 1;
+    }
+}
+
+fn update_endblock_and_endpartialdef_labels<'a>(
+    tokens: &mut [Token<'a>],
+    tree: &Tree,
+    paired_tags: &[String],
+) {
+    for block in tree.blocks() {
+        let (start, Some(end)) = (block.start(), block.end) else {
+            continue;
+        };
+        let (
+            Token::Block {
+                bits: start_bits,
+                span: start_span,
+                ..
+            },
+            Token::Block {
+                bits: end_bits,
+                span: end_span,
+                ..
+            },
+        ) = (&tokens[start], &tokens[end])
+        else {
+            continue;
+        };
+        if !matches!(start_bits[0].as_ref(), "block" | "partialdef")
+            && !paired_tags.iter().any(|tag| *tag == start_bits[0])
+        {
+            continue;
+        }
+        let Some(label) = start_bits.get(1).cloned() else {
+            continue;
+        };
+
+        let end_name = end_bits[0].clone();
+        let same_line = start_span.location.line == end_span.location.line;
+        let new_bits = if same_line && end_bits.len() == 2 {
+            vec![end_name]
+        } else if !same_line && end_bits.len() == 1 {
+            vec![end_name, label]
+        } else {
+            continue;
+        };
+        if let Token::Block { bits, .. } = &mut tokens[end] {
+            *bits = new_bits;
+        }
+    }
+}
+
+fn update_top_level_block_indentation<'a>(tokens: &mut [Token<'a>], tree: &Tree) {
+    let mut after_extends = false;
+
+    for node in &tree.root {
+        match node {
+            Node::Leaf(i) => {
+                if let Token::Block { bits, .. } = &tokens[*i] {
+                    if bits[0] != "extends" {
+                        return;
+                    }
+                    after_extends = true;
+                    unindent_token(tokens, *i);
+                }
+            }
+            Node::Block(block) => {
+                if !matches!(&tokens[block.start()], Token::Block { bits, .. } if bits[0] == "block")
+                {
+                    return;
+                }
+                if after_extends {
+                    unindent_token(tokens, block.start());
+                    if let Some(end) = block.end {
+                        unindent_token(tokens, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+static INDENTATION_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?m)^[ \t]+$\z").unwrap());
+
+fn unindent_token<'a>(tokens: &mut [Token<'a>], index: usize) {
+    if index > 0
+        && let Token::Text { contents, .. } = &mut tokens[index - 1]
+    {
+        let unindented = INDENTATION_LINE.replace_all(contents, "");
+        if unindented != *contents {
+            *contents = Cow::Owned(unindented.to_string());
+        }
+    }
+}
+
+fn update_top_level_block_spacing<'a>(tokens: &mut [Token<'a>], tree: &Tree, newline: &str) {
+    let mut has_extends = false;
+    let mut last_top_level_tag = None;
+
+    for node in &tree.root {
+        match node {
+            Node::Leaf(i) => {
+                if matches!(&tokens[*i], Token::Block { bits, .. } if bits[0] == "extends") {
+                    has_extends = true;
+                    last_top_level_tag = Some(*i);
+                }
+            }
+            Node::Block(block) => {
+                let start = block.start();
+                if !has_extends
+                    || !matches!(&tokens[start], Token::Block { bits, .. } if bits[0] == "block")
+                {
+                    continue;
+                }
+                if let Some(last_end) = last_top_level_tag
+                    && last_end + 2 == start
+                    && let Token::Text { contents, .. } = &mut tokens[start - 1]
+                    && contents.trim().is_empty()
+                {
+                    let double_newline = format!("{}{}", newline, newline);
+                    if contents.as_ref() != double_newline {
+                        *contents = Cow::Owned(double_newline);
+                    }
+                }
+                last_top_level_tag = Some(block.end.unwrap_or(start));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(content: &str, target_version: Option<(u8, u8)>) -> String {
+        let options = FormatOptions {
+            target_version: target_version.map(|(major, minor)| Version::new(major, minor)),
+            ..FormatOptions::default()
+        };
+        super::format(content, &options).unwrap()
+    }
+
+    // Version
+
+    #[test]
+    fn test_version_from_str() {
+        assert_eq!("5.2".parse(), Ok(Version::new(5, 2)));
+        assert_eq!("5".parse::<Version>(), Err(ParseVersionError));
+        assert_eq!("5.2.1".parse::<Version>(), Err(ParseVersionError));
+        assert_eq!("five.two".parse::<Version>(), Err(ParseVersionError));
+    }
+
+    // format_str
+
+    #[test]
+    fn test_format_str() {
+        let formatted = format_str("{{engine}}", &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, "{{ engine }}\n");
+    }
+
+    #[test]
+    fn test_format_str_unsupported_target_version() {
+        let options = FormatOptions {
+            target_version: Some(Version::new(4, 0)),
+            ..FormatOptions::default()
+        };
+        let result = format_str("{{ engine }}\n", &options);
+        assert!(matches!(
+            result,
+            Err(DjadeError::UnsupportedVersion(version)) if version == Version::new(4, 0)
+        ));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "unsupported target version 4.0"
+        );
+    }
+
+    #[test]
+    fn test_format_str_empty_block_tag() {
+        let formatted =
+            format_str("<p>\n  {%  %}\n  {{a}}\n</p>\n", &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, "<p>\n  {%  %}\n  {{ a }}\n</p>\n");
+    }
+
+    #[test]
+    fn test_format_str_empty_variable_tag() {
+        let formatted = format_str("é {{  }}{{a}}\n", &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, "é {{  }}{{ a }}\n");
+    }
+
+    #[test]
+    fn test_format_str_empty_tags_in_verbatim() {
+        let formatted = format_str(
+            "{% verbatim %}{% %}{{ }}{% endverbatim %}\n",
+            &FormatOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(formatted, "{% verbatim %}{% %}{{ }}{% endverbatim %}\n");
+    }
+
+    #[test]
+    fn test_djade_error_from_read() {
+        let error = DjadeError::from_read(
+            Some(Path::new("engine.html")),
+            io::Error::from(io::ErrorKind::InvalidData),
+        );
+        assert!(matches!(&error, DjadeError::Encoding { .. }));
+        assert_eq!(
+            error.to_string(),
+            "engine.html is non-UTF-8 (not supported)"
+        );
+
+        let error = DjadeError::from_read(None, io::Error::from(io::ErrorKind::InvalidData));
+        assert!(matches!(&error, DjadeError::Io { path: None, .. }));
+        assert!(error.to_string().starts_with("Error reading from stdin: "));
+    }
+
+    #[test]
+    fn test_format_str_fixer_selection() {
+        let options = FormatOptions {
+            target_version: Some(Version::new(5, 2)),
+            fixers: vec![Fixer::StaticLoadTags],
+            ..FormatOptions::default()
+        };
+        let formatted = format_str("{% load staticfiles %}\n{% trans 'Hi' %}\n", &options).unwrap();
+        assert_eq!(formatted, "{% load static %}\n{% trans 'Hi' %}\n");
+    }
+
+    #[test]
+    fn test_format_str_newline_auto() {
+        let formatted = format_str("{{a}}\r\n{{b}}\n", &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, "{{ a }}\r\n{{ b }}\r\n");
+    }
+
+    #[test]
+    fn test_format_str_newline_lf() {
+        let options = FormatOptions {
+            newline: NewlinePolicy::Lf,
+            ..FormatOptions::default()
+        };
+        let formatted = format_str("{{ a }}\r\n{{ b }}\r\n", &options).unwrap();
+        assert_eq!(formatted, "{{ a }}\n{{ b }}\n");
+    }
+
+    #[test]
+    fn test_format_str_newline_crlf() {
+        let options = FormatOptions {
+            newline: NewlinePolicy::CrLf,
+            ..FormatOptions::default()
+        };
+        let formatted = format_str("{{ a }}\n{{ b }}\r\n{{ c }}", &options).unwrap();
+        assert_eq!(formatted, "{{ a }}\r\n{{ b }}\r\n{{ c }}\r\n");
+    }
+
+    // diagnose_str
+
+    #[test]
+    fn test_diagnose_str() {
+        let diagnostics =
+            diagnose_str("{% if a %}\n{% endfor %}", &FormatOptions::default()).unwrap();
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    message: "unclosed 'if'".to_string(),
+                    location: Location { line: 1, column: 1 },
+                },
+                Diagnostic {
+                    message: "unexpected 'endfor'".to_string(),
+                    location: Location { line: 2, column: 1 },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnose_str_if_expressions() {
+        let diagnostics = diagnose_str(
+            "{% if a and %}\n{% elif or b %}\n{% elif c==d %}\n{% endif %}",
+            &FormatOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "unexpected end of expression in if tag at line 1, column 1",
+                "not expecting 'or' in this position in if tag at line 2, column 1",
+                "could not parse the remainder: '==d' from 'c==d' at line 3, column 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnose_str_unparsed_variables() {
+        let diagnostics = diagnose_str(
+            "{{ x|default: \"a\" }}\n{{ |upper }}\n{{ !x }}\n{{ a|b:c }}",
+            &FormatOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "could not parse the remainder: ': \"a\"' from 'x|default: \"a\"' at line 1, column 1",
+                "could not find variable at start of '|upper' at line 2, column 1",
+                "could not parse the remainder: '!x' from '!x' at line 3, column 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnose_str_unparsed_if_literal() {
+        let diagnostics =
+            diagnose_str("{% if a|length: 1 %}{% endif %}", &FormatOptions::default()).unwrap();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec!["could not parse the remainder: ':' from 'a|length:' at line 1, column 1"]
+        );
+    }
+
+    #[test]
+    fn test_diagnose_str_multiline_tags() {
+        let diagnostics = diagnose_str(
+            "<p>\n  {{ a\n  }} {% if b\n %}{% endif %}",
+            &FormatOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "multi-line tag rendered as text at line 2, column 3",
+                "multi-line tag rendered as text at line 3, column 6",
+                "unexpected 'endif' at line 4, column 4",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnose_str_unclosed_multiline_tags() {
+        let diagnostics = diagnose_str(
+            "{% if a\n  and b {{ c }}\n{# d\n",
+            &FormatOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "unclosed multi-line tag rendered as text at line 1, column 1",
+                "unclosed multi-line tag rendered as text at line 3, column 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnose_str_unclosed_single_line_tag() {
+        let diagnostics = diagnose_str("{{ a }} {% if b", &FormatOptions::default()).unwrap();
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_diagnose_str_multiline_tags_in_verbatim_and_comment() {
+        let diagnostics = diagnose_str(
+            "{% verbatim %}{{ a\n}}{% endverbatim %}{% comment %}{% b\n%}{% endcomment %}",
+            &FormatOptions::default(),
+        )
+        .unwrap();
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_diagnose_str_paired_tags() {
+        let template = "{% slot a %}{% if b %}{% endslot %}";
+        let options = FormatOptions {
+            paired_tags: vec!["slot".to_string()],
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            diagnose_str(template, &options)
+                .unwrap()
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec!["expected 'endif', found 'endslot' at line 1, column 23"]
+        );
+        assert_eq!(
+            diagnose_str(template, &FormatOptions::default())
+                .unwrap()
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec!["unclosed 'if' at line 1, column 13"]
+        );
+    }
+
+    #[test]
+    fn test_diagnose_str_empty_tags() {
+        let diagnostics = diagnose_str(
+            "<p>\n  {% %}\n</p>\né {{  }}{# #}\n{% verbatim %}{% %}{{ }}{% endverbatim %}",
+            &FormatOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "empty block tag at line 2, column 3",
+                "empty variable tag at line 4, column 3",
+            ]
+        );
+    }
+
+    // check_str
+
+    #[test]
+    fn test_check_str_formatted() {
+        assert!(check_str("{{ engine }}\n", &FormatOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn test_check_str_unformatted() {
+        assert!(!check_str("{{engine}}\n", &FormatOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn test_fixer_opt_in() {
+        assert!(Fixer::MultilineComments.is_opt_in());
+        assert!(Fixer::MultilineTags.is_opt_in());
+        assert!(!Fixer::LengthIs.is_opt_in());
+        assert!(
+            !FormatOptions::default()
+                .fixers
+                .contains(&Fixer::MultilineTags)
+        );
+        assert_eq!(
+            Fixer::ALL.iter().filter(|fixer| !fixer.is_opt_in()).count(),
+            Fixer::DEFAULT.len()
+        );
+    }
+
+    #[test]
+    fn test_fixer_versions_supported() {
+        for fixer in Fixer::ALL {
+            if let Some(version) = fixer.min_version() {
+                assert!(
+                    version.is_supported(),
+                    "{} requires unsupported version {}",
+                    fixer.name(),
+                    version
+                );
+            }
+        }
+    }
+
+    // lex
+
+    fn spans(tokens: &[Token]) -> Vec<(usize, usize, usize, usize)> {
+        tokens
+            .iter()
+            .map(|token| {
+                let span = token.span();
+                (
+                    span.start,
+                    span.end,
+                    span.location.line,
+                    span.location.column,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lex_spans() {
+        let tokens = lex("é {{ a }}\n{% if\nb %}ü{# c #}");
+        assert_eq!(
+            spans(&tokens),
+            vec![(0, 3, 1, 1), (3, 10, 1, 3), (10, 23, 1, 10), (23, 30, 3, 6)]
+        );
+    }
+
+    #[test]
+    fn test_lex_spans_verbatim() {
+        let tokens = lex("{% verbatim %}\n{{ a }}{% endverbatim %}");
+        assert_eq!(
+            spans(&tokens),
+            vec![
+                (0, 14, 1, 1),
+                (14, 15, 1, 15),
+                (15, 22, 2, 1),
+                (22, 39, 2, 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spans_survive_fixers() {
+        let mut tokens = lex("{% load i18n %}\n  {% trans 'Hi' %}");
+        let before = spans(&tokens);
+        Fixer::TranslationTags.apply(&mut tokens, &[]);
+        assert!(matches!(&tokens[2], Token::Block { bits, .. } if bits[0] == "translate"));
+        assert_eq!(spans(&tokens), before);
+        assert_eq!(before[2], (18, 34, 2, 3));
+    }
+
+    #[test]
+    fn test_span_empty_at() {
+        let span = Span::empty_at("a\nbé", 5);
+        assert_eq!((span.start, span.end), (5, 5));
+        assert_eq!(span.location, Location { line: 2, column: 3 });
+    }
+
+    #[test]
+    fn test_lex_tag_argument() {
+        assert_eq!(
+            lex_tag_argument("title=page.title|default:''"),
+            Some(TagArgument {
+                key: Some("title"),
+                filter_expression: FilterExpression {
+                    var: Expression::Variable(Cow::Borrowed("page.title")),
+                    filters: vec![Filter {
+                        name: Cow::Borrowed("default"),
+                        arg: Some(Expression::Constant(Cow::Borrowed("''"))),
+                    }],
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_lex_tag_argument_not_keyword() {
+        let argument = lex_tag_argument("\"a=b\"|upper").unwrap();
+        assert_eq!(argument.key, None);
+        assert_eq!(
+            argument.filter_expression.var,
+            Expression::Constant(Cow::Borrowed("\"a=b\""))
+        );
+        assert_eq!(lex_tag_argument("a==b"), None);
+    }
+
+    #[test]
+    fn test_lex_tag_argument_unparsed() {
+        assert_eq!(lex_tag_argument("a|"), None);
+        assert_eq!(lex_tag_argument("key=|upper"), None);
+    }
+
+    // lex, compared with Django’s regular expressions
+
+    static TAG_RE: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(\{%.*?%\}|\{\{.*?\}\}|\{#.*?#\})").unwrap());
+
+    static FILTER_RE: LazyLock<Regex> = LazyLock::new(|| {
+        let constant_string = format!(
+            r#"(?x)
+            (?:{i18n_open}{strdq}{i18n_close}|
+               {i18n_open}{strsq}{i18n_close}|
+               {strdq}|
+               {strsq})
+        "#,
+            strdq = r#""[^"\\]*(?:\\.[^"\\]*)*""#,
+            strsq = r#"'[^'\\]*(?:\\.[^'\\]*)*'"#,
+            i18n_open = regex::escape("_("),
+            i18n_close = regex::escape(")"),
+        );
+
+        regex::RegexBuilder::new(&format!(
+            r#"(?x)
+            ^(?P<constant>{constant})|
+            ^(?P<var>[{var_chars}]+)|
+             (?:\s*{filter_sep}\s*
+                 (?P<filter_name>\w+)
+                     (?:{arg_sep}
+                         (?:
+                          (?P<constant_arg>{constant})|
+                          (?P<var_arg>[{var_chars}]+)
+                         )
+                     )?
+             )"#,
+            constant = constant_string,
+            var_chars = r"\w\.\+-",
+            filter_sep = regex::escape("|"),
+            arg_sep = regex::escape(":"),
+        ))
+        .build()
+        .unwrap()
+    });
+
+    static SMART_SPLIT_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r#"(?x)
+            ((?:
+                [^\s'"]*
+                (?:
+                    (?:"(?:[^"\\]|\\.)*" | '(?:[^'\\]|\\.)*')
+                    [^\s'"]*
+                )+
+            ) | \S+)"#,
+        )
+        .unwrap()
+    });
+
+    fn regex_tags(template_string: &str) -> Vec<(usize, usize)> {
+        TAG_RE
+            .find_iter(template_string)
+            .map(|tag| (tag.start(), tag.end()))
+            .collect()
+    }
+
+    fn regex_lex_filter_expression(expr: &str) -> FilterExpression<'_> {
+        let unparsed = FilterExpression {
+            var: Expression::Unparsed(Cow::Borrowed(expr)),
+            filters: Vec::new(),
+        };
+        let mut filter_expression = unparsed.clone();
+        let mut upto = 0;
+        let mut variable = false;
+        for captures in FILTER_RE.captures_iter(expr) {
+            if upto != captures.get(0).unwrap().start() {
+                return unparsed;
+            }
+            let expression = |constant, var| {
+                if let Some(constant) = captures.name(constant) {
+                    Some(Expression::Constant(Cow::Borrowed(constant.as_str())))
+                } else {
+                    captures
+                        .name(var)
+                        .map(|var| Expression::Variable(Cow::Borrowed(var.as_str())))
+                }
+            };
+            if !variable {
+                if let Some(var) = expression("constant", "var") {
+                    filter_expression.var = var;
+                }
+                variable = true;
+            } else {
+                filter_expression.filters.push(Filter {
+                    name: Cow::Borrowed(captures.name("filter_name").unwrap().as_str()),
+                    arg: expression("constant_arg", "var_arg"),
+                });
+            }
+            upto = captures.get(0).unwrap().end();
+        }
+        if upto != expr.len() || matches!(filter_expression.var, Expression::Unparsed(_)) {
+            return unparsed;
+        }
+        filter_expression
+    }
+
+    fn regex_smart_split(text: &str) -> Vec<&str> {
+        SMART_SPLIT_RE
+            .find_iter(text)
+            .map(|bit| bit.as_str())
+            .collect()
+    }
+
+    fn scanned_tags(template_string: &str) -> Vec<(usize, usize)> {
+        let mut tags = Vec::new();
+        let mut from = 0;
+        while let Some((start, end)) = next_tag(template_string, from) {
+            tags.push((start, end));
+            from = end;
+        }
+        tags
+    }
+
+    const LEX_CASES: &[&str] = &[
+        "",
+        "{% if a %}{{ b }}{# c #}{% endif %}",
+        "{%%}{{}}{##}{%}{{}{#}",
+        "{{{ a }}}",
+        "{% a\n%}{{ b }}",
+        "{# a #}} #}",
+        "{% a %}\r\n{{ b }}",
+        "{{ x|default:'a \\' b'|upper }}",
+        "{{ _(\"Rail\")|lower:_('x') }}",
+        "{{ a|b:c|d:\"e\\\nf\" }}",
+        "{{ |upper|lower }}",
+        "a|b:\"e\\\nf\"",
+        "_('a\\\nb')|c",
+        "{{ a | b : c }}",
+        "{{ a |b:}}",
+        "{{ é.ü|naïve:ß² }}",
+        "{{ a\u{301}|b\u{a0}|\u{2003}c }}",
+        "{% with a=\"b c\"d'e' f %}",
+        "{% x 'a b\\' \"c d %}",
+        "{% x \"a\\\nb\" c %}",
+        "{% trans _(\"a b\") %}{% x _('c' d) %}",
+    ];
+
+    const LEX_FRAGMENTS: &[&str] = &[
+        "{", "}", "%", "#", "{%", "%}", "{{", "}}", "{#", "#}", " ", " ", "\n", "\r", "\t", "\"",
+        "'", "\\", "|", "|", ":", "_(", ")", "a", "b1", ".", "+", "-", "é", "\u{301}", "²",
+        "\u{a0}", "if", "upper", "trans",
+    ];
+
+    /// Deterministic pseudo-random strings made of fragments that exercise
+    /// the lexer’s edge cases.
+    fn lex_inputs() -> impl Iterator<Item = String> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        let generated = (0..20_000).map(move |_| {
+            let length = next() % 16;
+            (0..length)
+                .map(|_| LEX_FRAGMENTS[next() % LEX_FRAGMENTS.len()])
+                .collect()
+        });
+        LEX_CASES
+            .iter()
+            .map(|case| case.to_string())
+            .chain(generated)
+    }
+
+    #[test]
+    fn test_lex_tags_match_regex() {
+        for input in lex_inputs() {
+            assert_eq!(scanned_tags(&input), regex_tags(&input), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_lex_filter_expression_matches_regex() {
+        for input in lex_inputs() {
+            for expr in [input.as_str(), input.trim()] {
+                assert_eq!(
+                    lex_filter_expression(expr),
+                    regex_lex_filter_expression(expr),
+                    "{:?}",
+                    expr
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_lex_smart_split_matches_regex() {
+        for input in lex_inputs() {
+            assert_eq!(
+                smart_split(&input).collect::<Vec<_>>(),
+                regex_smart_split(&input),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_lex_smart_split_quoted() {
+        assert_eq!(
+            smart_split(r#"with a="b c"d'e' f 'g\' h"#).collect::<Vec<_>>(),
+            vec!["with", r#"a="b c"d'e'"#, "f", r"'g\'", "h"]
+        );
+    }
+
+    #[test]
+    fn test_lex_filter_expression_unparsed() {
+        assert_eq!(
+            lex_filter_expression("a|"),
+            FilterExpression {
+                var: Expression::Unparsed(Cow::Borrowed("a|")),
+                filters: vec![],
+            }
+        );
+    }
+
+    // detect_newline
+
+    #[test]
+    fn test_detect_newline_defaults_to_line_feed() {
+        assert_eq!(detect_newline(""), "\n");
+    }
+
+    #[test]
+    fn test_detect_newline_with_carriage_return_first() {
+        assert_eq!(detect_newline("foo\r\nbar\n"), "\r\n");
+    }
+
+    #[test]
+    fn test_detect_newline_with_line_feed_first() {
+        assert_eq!(detect_newline("foo\nbar\r\n"), "\n");
+    }
+
+    // Fixers
+
+    // fix_multiline_tags
+
+    fn format_joining_multiline_tags(content: &str) -> String {
+        let options = FormatOptions {
+            fixers: vec![Fixer::MultilineTags],
+            ..FormatOptions::default()
+        };
+        format_str(content, &options).unwrap()
+    }
+
+    #[test]
+    fn test_format_multiline_tags_left_by_default() {
+        let formatted = format("{% if a\n   and b %}x{% endif %}\n", None);
+        assert_eq!(formatted, "{% if a\n   and b %}x{% endif %}\n");
+    }
+
+    #[test]
+    fn test_format_multiline_tags_joined() {
+        let formatted =
+            format_joining_multiline_tags("{% if a\n   and b %}x{% endif %}\n{{ name\n}}\n");
+        assert_eq!(formatted, "{% if a and b %}x{% endif %}\n{{ name }}\n");
+    }
+
+    #[test]
+    fn test_format_multiline_tags_joined_crlf() {
+        let formatted = format_joining_multiline_tags("<p>\r\n{#  a\r\n  b #}\r\n</p>\r\n");
+        assert_eq!(formatted, "<p>\r\n{# a b #}\r\n</p>\r\n");
+    }
+
+    #[test]
+    fn test_format_multiline_tags_in_verbatim_not_joined() {
+        let formatted = format_joining_multiline_tags("{% verbatim %}{{ a\n}}{% endverbatim %}\n");
+        assert_eq!(formatted, "{% verbatim %}{{ a\n}}{% endverbatim %}\n");
+    }
+
+    #[test]
+    fn test_format_multiline_tags_unclosed_not_joined() {
+        let formatted = format_joining_multiline_tags("{% if a\n   and b\n{{ c }}\n");
+        assert_eq!(formatted, "{% if a\n   and b\n{{ c }}\n");
+    }
+
+    #[test]
+    fn test_format_multiline_tags_with_fixers() {
+        let options = FormatOptions {
+            target_version: Some(Version::new(3, 1)),
+            fixers: Fixer::ALL.to_vec(),
+            ..FormatOptions::default()
+        };
+        let formatted = format_str("{% load i18n %}\n{% trans\n'Hi' %}\n", &options).unwrap();
+        assert_eq!(formatted, "{% load i18n %}\n{% translate 'Hi' %}\n");
+    }
+
+    // multiline comments
+
+    fn format_converting_multiline_comments(content: &str, fixers: Vec<Fixer>) -> String {
+        let options = FormatOptions {
+            fixers,
+            ..FormatOptions::default()
+        };
+        format_str(content, &options).unwrap()
+    }
+
+    #[test]
+    fn test_format_multiline_comment_converted() {
+        let formatted = format_converting_multiline_comments(
+            "<p>\n{# Rail\n   engine #}\n</p>\n",
+            vec![Fixer::MultilineComments],
+        );
+        assert_eq!(
+            formatted,
+            "<p>\n{% comment %} Rail\n   engine {% endcomment %}\n</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_format_multiline_comment_converted_before_joining() {
+        let formatted = format_converting_multiline_comments(
+            "{# a\nb #}{{ c\n}}\n",
+            vec![Fixer::MultilineComments, Fixer::MultilineTags],
+        );
+        assert_eq!(formatted, "{% comment %} a\nb {% endcomment %}{{ c }}\n");
+    }
+
+    #[test]
+    fn test_format_multiline_comment_other_tags_left() {
+        let formatted = format_converting_multiline_comments(
+            "{% if a\n %}{# b #}\n",
+            vec![Fixer::MultilineComments],
+        );
+        assert_eq!(formatted, "{% if a\n %}{# b #}\n");
+    }
+
+    #[test]
+    fn test_format_multiline_comment_containing_endcomment_left() {
+        let formatted = format_converting_multiline_comments(
+            "{# a\n{% endcomment %} #}\n",
+            vec![Fixer::MultilineComments],
+        );
+        assert_eq!(formatted, "{# a\n{% endcomment %} #}\n");
+    }
+
+    #[test]
+    fn test_format_multiline_comment_in_comment_block_left() {
+        let formatted = format_converting_multiline_comments(
+            "{% comment %}{# a\nb #}{% endcomment %}\n",
+            vec![Fixer::MultilineComments],
+        );
+        assert_eq!(formatted, "{% comment %}{# a\nb #}{% endcomment %}\n");
+    }
+
+    // migrate_length_is
+
+    #[test]
+    fn test_length_is_not_migrated_old_django() {
+        let formatted = format("{% if eggs|length_is:1 %}{% endif %}\n", Some((4, 1)));
+        assert_eq!(formatted, "{% if eggs|length_is:1 %}{% endif %}\n");
+    }
+
+    #[test]
+    fn test_length_is_migrated() {
+        let formatted = format("{% if eggs|length_is:1 %}{% endif %}\n", Some((4, 2)));
+        assert_eq!(formatted, "{% if eggs|length == 1 %}{% endif %}\n");
+    }
+
+    #[test]
+    fn test_length_is_not_migrated_when_no_version_specified() {
+        let formatted = format("{% if eggs|length_is:1 %}{% endif %}\n", None);
+        assert_eq!(formatted, "{% if eggs|length_is:1 %}{% endif %}\n");
+    }
+
+    #[test]
+    fn test_length_is_migrated_with_variable() {
+        let formatted = format("{% if eggs|length_is:n %}{% endif %}\n", Some((4, 2)));
+        assert_eq!(formatted, "{% if eggs|length == n %}{% endif %}\n");
+    }
+
+    #[test]
+    fn test_length_is_migrated_with_complex_variable() {
+        let formatted = format(
+            "{% if basket.eggs|length_is:1 %}{% endif %}\n",
+            Some((4, 2)),
+        );
+        assert_eq!(formatted, "{% if basket.eggs|length == 1 %}{% endif %}\n");
+    }
+
+    #[test]
+    fn test_length_is_not_migrated_in_variable_tag() {
+        let formatted = format("{{ eggs|length_is:1 }}\n", Some((4, 2)));
+        assert_eq!(formatted, "{{ eggs|length_is:1 }}\n");
+    }
+
+    #[test]
+    fn test_length_is_migrated_with_other_conditions() {
+        let formatted = format(
+            "{% if eggs|length_is:1 and spam or not ham|length_is:n %}{% endif %}\n",
+            Some((4, 2)),
+        );
+        assert_eq!(
+            formatted,
+            "{% if eggs|length == 1 and spam or not ham|length == n %}{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_length_is_migrated_in_elif() {
+        let formatted = format(
+            "{% if a %}{% elif eggs|length_is:2 %}{% endif %}\n",
+            Some((4, 2)),
+        );
+        assert_eq!(
+            formatted,
+            "{% if a %}{% elif eggs|length == 2 %}{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_length_is_migrated_after_other_filters() {
+        let formatted = format("{% if eggs|first|length_is:1 %}{% endif %}\n", Some((4, 2)));
+        assert_eq!(formatted, "{% if eggs|first|length == 1 %}{% endif %}\n");
+    }
+
+    #[test]
+    fn test_length_is_not_migrated_when_compared() {
+        let formatted = format(
+            "{% if eggs|length_is:1 == True %}{% endif %}\n",
+            Some((4, 2)),
+        );
+        assert_eq!(formatted, "{% if eggs|length_is:1 == True %}{% endif %}\n");
+    }
+
+    #[test]
+    fn test_length_is_not_migrated_before_other_filters() {
+        let formatted = format("{% if eggs|length_is:1|yesno %}{% endif %}\n", Some((4, 2)));
+        assert_eq!(formatted, "{% if eggs|length_is:1|yesno %}{% endif %}\n");
+    }
+
+    #[test]
+    fn test_length_is_not_migrated_with_constant() {
+        let formatted = format("{% if eggs|length_is:'1' %}{% endif %}\n", Some((4, 2)));
+        assert_eq!(formatted, "{% if eggs|length_is:'1' %}{% endif %}\n");
+    }
+
+    #[test]
+    fn test_length_is_not_migrated_in_other_tags() {
+        let formatted = format(
+            "{% with total=eggs|length_is:3 %}{% endwith %}\n",
+            Some((4, 2)),
+        );
+        assert_eq!(
+            formatted,
+            "{% with total=eggs|length_is:3 %}{% endwith %}\n"
+        );
+    }
+
+    // migrate_empty_json_script
+
+    #[test]
+    fn test_migrate_empty_json_script_in_tag_argument() {
+        let formatted = format("{% firstof data|json_script:'' %}\n", Some((4, 1)));
+        assert_eq!(formatted, "{% firstof data|json_script %}\n");
+    }
+
+    #[test]
+    fn test_migrate_empty_json_script_in_tag_keyword_argument() {
+        let formatted = format(
+            "{% include \"x.html\" with data=d|json_script:\"\" title=t|default:'' %}\n",
+            Some((4, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% include \"x.html\" with data=d|json_script title=t|default:'' %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_empty_json_script_in_comment_tag_unchanged() {
+        let formatted = format(
+            "{% comment data|json_script:'' %}{% endcomment %}\n",
+            Some((4, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% comment data|json_script:'' %}{% endcomment %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_empty_json_script_double_quotes() {
+        let formatted = format("{{ egg_data|json_script:\"\" }}\n", Some((4, 1)));
+        assert_eq!(formatted, "{{ egg_data|json_script }}\n");
+    }
+
+    #[test]
+    fn test_migrate_empty_json_script_single_quotes() {
+        let formatted = format("{{ egg_data|json_script:'' }}\n", Some((4, 1)));
+        assert_eq!(formatted, "{{ egg_data|json_script }}\n");
+    }
+
+    #[test]
+    fn test_migrate_empty_json_script_not_empty() {
+        let formatted = format("{{ egg_data|json_script:'egg_id' }}\n", Some((4, 1)));
+        assert_eq!(formatted, "{{ egg_data|json_script:'egg_id' }}\n");
+    }
+
+    #[test]
+    fn test_migrate_empty_json_script_old_django() {
+        let formatted = format("{{ egg_data|json_script:\"\" }}\n", Some((4, 0)));
+        assert_eq!(formatted, "{{ egg_data|json_script:\"\" }}\n");
+    }
+
+    #[test]
+    fn test_migrate_empty_json_script_no_version() {
+        let formatted = format("{{ egg_data|json_script:\"\" }}\n", None);
+        assert_eq!(formatted, "{{ egg_data|json_script:\"\" }}\n");
+    }
+
+    #[test]
+    fn test_migrate_empty_json_script_after_another_filter() {
+        let formatted = format("{{ egg_data|upper|json_script:\"\" }}\n", Some((4, 1)));
+        assert_eq!(formatted, "{{ egg_data|upper|json_script }}\n");
+    }
+
+    #[test]
+    fn test_migrate_empty_json_script_before_another_filter() {
+        let formatted = format("{{ egg_data|json_script:\"\"|safe }}\n", Some((4, 1)));
+        assert_eq!(formatted, "{{ egg_data|json_script|safe }}\n");
+    }
+
+    // migrate_ifequal_tags
+
+    #[test]
+    fn test_format_ifequal_old_django_not_migrated() {
+        let formatted = format("{% ifequal a b %}\n{% endifequal %}\n", Some((3, 0)));
+        assert_eq!(formatted, "{% ifequal a b %}\n{% endifequal %}\n");
+    }
+
+    #[test]
+    fn test_format_ifequal_too_few_args_not_migrated() {
+        let formatted = format("{% ifequal a %}\n{% endifequal %}\n", Some((3, 1)));
+        assert_eq!(formatted, "{% ifequal a %}\n{% endifequal %}\n");
+    }
+
+    #[test]
+    fn test_format_ifequal_too_many_args_not_migrated() {
+        let formatted = format("{% ifequal a b c %}\n{% endifequal %}\n", Some((3, 1)));
+        assert_eq!(formatted, "{% ifequal a b c %}\n{% endifequal %}\n");
+    }
+
+    #[test]
+    fn test_format_ifequal_incorrect_pairing_start_not_migrated() {
+        let formatted = format("{% ifequal a b %}\n{% endif %}\n", Some((3, 1)));
+        assert_eq!(formatted, "{% ifequal a b %}\n{% endif %}\n");
+    }
+
+    #[test]
+    fn test_format_ifequal_incorrect_pairing_end_not_migrated() {
+        let formatted = format("{% if a == b %}\n{% endifequal %}\n", Some((3, 1)));
+        assert_eq!(formatted, "{% if a == b %}\n{% endifequal %}\n");
+    }
+
+    #[test]
+    fn test_format_ifequal_with_else_migrated() {
+        let formatted = format(
+            "{% ifequal a b %}\nyes\n{% else %}\nno\n{% endifequal %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% if a == b %}\nyes\n{% else %}\nno\n{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_ifequal_inside_other_tags_migrated() {
+        let formatted = format(
+            "{% for a in b %}{% ifnotequal a c %}{{ a }}{% endifnotequal %}{% endfor %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% for a in b %}{% if a != c %}{{ a }}{% endif %}{% endfor %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_ifequal_migrated() {
+        let formatted = format("{% ifequal a b %}\n{% endifequal %}\n", Some((3, 1)));
+        assert_eq!(formatted, "{% if a == b %}\n{% endif %}\n");
+    }
+
+    #[test]
+    fn test_format_ifequal_migrated_constant() {
+        let formatted = format(
+            "{% ifequal a 'the golden goose' %}\n{% endifequal %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(formatted, "{% if a == 'the golden goose' %}\n{% endif %}\n");
+    }
+
+    #[test]
+    fn test_format_ifequal_migrated_with_dots() {
+        let formatted = format(
+            "{% ifequal user.name author.name %}\n{% endifequal %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% if user.name == author.name %}\n{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_ifequal_migrated_with_filters() {
+        let formatted = format(
+            "{% ifequal user.name|lower 'admin' %}\n{% endifequal %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% if user.name|lower == 'admin' %}\n{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_ifnotequal_old_django_not_migrated() {
+        let formatted = format("{% ifnotequal a b %}\n{% endifnotequal %}\n", Some((3, 0)));
+        assert_eq!(formatted, "{% ifnotequal a b %}\n{% endifnotequal %}\n");
+    }
+
+    #[test]
+    fn test_format_ifnotequal_migrated() {
+        let formatted = format("{% ifnotequal a b %}\n{% endifnotequal %}\n", Some((3, 1)));
+        assert_eq!(formatted, "{% if a != b %}\n{% endif %}\n");
+    }
+
+    #[test]
+    fn test_format_ifequal_migrated_with_translated_string() {
+        let formatted = format(
+            "{% ifequal message _('Welcome') %}\n{% endifequal %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(formatted, "{% if message == _('Welcome') %}\n{% endif %}\n");
+    }
+
+    #[test]
+    fn test_format_ifequal_nested_migrated() {
+        let formatted = format(
+            "{% ifequal a b %}\n{% ifnotequal b c %}\n{% endifnotequal %}\n{% endifequal %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% if a == b %}\n{% if b != c %}\n{% endif %}\n{% endif %}\n"
+        );
+    }
+
+    // migrate_translation_tags
+
+    #[test]
+    fn test_trans_not_migrated_old_django() {
+        let formatted = format(
+            "{% load trans from i18n %}\n{% trans 'Hello' %}\n",
+            Some((3, 0)),
+        );
+        assert_eq!(
+            formatted,
+            "{% load trans from i18n %}\n{% trans 'Hello' %}\n"
+        );
+    }
+
+    #[test]
+    fn test_trans_migrated() {
+        let formatted = format(
+            "{% load trans from i18n %}\n{% trans 'Hello' %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% load translate from i18n %}\n{% translate 'Hello' %}\n"
+        );
+    }
+
+    #[test]
+    fn test_blocktrans_not_migrated_old_django() {
+        let formatted = format(
+            "{% load blocktrans from i18n %}\n{% blocktrans %}Hello{% endblocktrans %}\n",
+            Some((3, 0)),
+        );
+        assert_eq!(
+            formatted,
+            "{% load blocktrans from i18n %}\n{% blocktrans %}Hello{% endblocktrans %}\n"
+        );
+    }
+
+    #[test]
+    fn test_blocktrans_migrated() {
+        let formatted = format(
+            "{% load blocktrans from i18n %}\n{% blocktrans %}Hello{% endblocktrans %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% load blocktranslate from i18n %}\n{% blocktranslate %}Hello{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_blocktrans_with_args_migrated() {
+        let formatted = format(
+            "{% blocktrans with name='John' %}Hello {{ name }}{% endblocktrans %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate with name='John' %}Hello {{ name }}{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_multiple_translation_tags_migrated() {
+        let formatted = format(
+            "{% trans 'Hello' %}\n{% blocktrans %}World{% endblocktrans %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% translate 'Hello' %}\n{% blocktranslate %}World{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_translation_tags_not_migrated_when_no_version_specified() {
+        let formatted = format(
+            "{% trans 'Hello' %}\n{% blocktrans %}World{% endblocktrans %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% trans 'Hello' %}\n{% blocktrans %}World{% endblocktrans %}\n"
+        );
+    }
+
+    #[test]
+    fn test_translation_tags_within_other_blocks() {
+        let formatted = format(
+            "{% if condition %}\n  {% trans 'Hello' %}\n{% endif %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% if condition %}\n  {% translate 'Hello' %}\n{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_translation_tags_with_filters() {
+        let formatted = format(
+            "{% blocktrans trimmed %}\n  Hello\n{% endblocktrans %}\n",
+            Some((3, 1)),
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate trimmed %}\n  Hello\n{% endblocktranslate %}\n"
+        );
+    }
+
+    // migrate_static_load_tags
+
+    #[test]
+    fn test_admin_static_migrated() {
+        let formatted = format("{% load admin_static %}\n", Some((2, 1)));
+        assert_eq!(formatted, "{% load static %}\n");
+    }
+
+    #[test]
+    fn test_admin_static_not_migrated() {
+        let formatted = format("{% load admin_static %}\n", Some((2, 0)));
+        assert_eq!(formatted, "{% load admin_static %}\n");
+    }
+
+    #[test]
+    fn test_staticfiles_migrated() {
+        let formatted = format("{% load staticfiles %}\n", Some((2, 1)));
+        assert_eq!(formatted, "{% load static %}\n");
+    }
+
+    #[test]
+    fn test_staticfiles_not_migrated() {
+        let formatted = format("{% load staticfiles %}\n", Some((2, 0)));
+        assert_eq!(formatted, "{% load staticfiles %}\n");
+    }
+
+    #[test]
+    fn test_from_admin_static_migrated() {
+        let formatted = format("{% load static from admin_static %}\n", Some((2, 1)));
+        assert_eq!(formatted, "{% load static from static %}\n");
+    }
+
+    #[test]
+    fn test_from_admin_static_not_migrated() {
+        let formatted = format("{% load static from admin_static %}\n", Some((2, 0)));
+        assert_eq!(formatted, "{% load static from admin_static %}\n");
+    }
+
+    #[test]
+    fn test_from_staticfiles_migrated() {
+        let formatted = format("{% load static from staticfiles %}\n", Some((2, 1)));
+        assert_eq!(formatted, "{% load static from static %}\n");
+    }
+
+    #[test]
+    fn test_from_staticfiles_not_migrated() {
+        let formatted = format("{% load static from staticfiles %}\n", Some((2, 0)));
+        assert_eq!(formatted, "{% load static from staticfiles %}\n");
+    }
+
+    // migrate_assignments
+
+    #[test]
+    fn test_migrate_assignments_with_single_legacy() {
+        let formatted = format(
+            "{% with engines.count as total %}{{ total }}{% endwith %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% with total=engines.count %}{{ total }}{% endwith %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_with_multiple_legacy() {
+        let formatted = format(
+            "{% with engines.count as total and cars.count as vehicles %}{{ total }} {{ vehicles }}{% endwith %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% with total=engines.count vehicles=cars.count %}{{ total }} {{ vehicles }}{% endwith %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_with_mixed() {
+        let formatted = format(
+            "{% with engines.count as total and vehicles=cars.count %}{{ total }} {{ vehicles }}{% endwith %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% with total=engines.count vehicles=cars.count %}{{ total }} {{ vehicles }}{% endwith %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_with_new_unchanged() {
+        let formatted = format(
+            "{% with total=engines.count %}{{ total }}{% endwith %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% with total=engines.count %}{{ total }}{% endwith %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_with_nested() {
+        let formatted = format(
+            "{% with outer=1 %}{% with 2 as inner %}{{ outer }} {{ inner }}{% endwith %}{% endwith %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% with outer=1 %}{% with inner=2 %}{{ outer }} {{ inner }}{% endwith %}{% endwith %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_with_unknown_end() {
+        let formatted = format(
+            "{% with 'Go' as exclamation loud %}{{ exclamation }}{% endwith %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% with exclamation='Go' loud %}{{ exclamation }}{% endwith %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_with_unknown_start() {
+        let formatted = format(
+            "{% with loud 'Go' as exclamation %}{{ exclamation }}{% endwith %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% with loud 'Go' as exclamation %}{{ exclamation }}{% endwith %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_blocktrans_legacy_with() {
+        let formatted = format(
+            "{% blocktrans with engine.name as name %}Hello {{ name }}{% endblocktrans %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktrans with name=engine.name %}Hello {{ name }}{% endblocktrans %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_blocktranslate_legacy_with() {
+        let formatted = format(
+            "{% blocktranslate with engine.name as name %}Hello {{ name }}{% endblocktranslate %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate with name=engine.name %}Hello {{ name }}{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_blocktranslate_modern_with() {
+        let formatted = format(
+            "{% blocktranslate with name=engine.name %}Hello {{ name }}{% endblocktranslate %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate with name=engine.name %}Hello {{ name }}{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_blocktranslate_legacy_count() {
+        let formatted = format(
+            "{% blocktranslate count engines.count as total %}{{ total }} user{% plural %}{{ total }} users{% endblocktranslate %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate count total=engines.count %}{{ total }} user{% plural %}{{ total }} users{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_blocktranslate_modern_count() {
+        let formatted = format(
+            "{% blocktranslate count total=engines.count %}{{ total }} engine{% plural %}{{ total }} engines{% endblocktranslate %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate count total=engines.count %}{{ total }} engine{% plural %}{{ total }} engines{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_blocktranslate_legacy_with_and_count() {
+        let formatted = format(
+            "{% blocktranslate with engine.name as name count engines.count as total %}Hello {{ name }}, there is {{ total }} engine{% plural %}Hello {{ name }}, there are {{ total }} engines{% endblocktranslate %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate with name=engine.name count total=engines.count %}Hello {{ name }}, there is {{ total }} engine{% plural %}Hello {{ name }}, there are {{ total }} engines{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_blocktranslate_modern_with_and_count() {
+        let formatted = format(
+            "{% blocktranslate with name=engine.name count total=engines.count %}Hello {{ name }}, there is {{ total }} engines{% plural %}Hello {{ name }}, there are {{ total }} engines{% endblocktranslate %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate with name=engine.name count total=engines.count %}Hello {{ name }}, there is {{ total }} engines{% plural %}Hello {{ name }}, there are {{ total }} engines{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_blocktranslate_multiple_with_legacy() {
+        let formatted = format(
+            "{% blocktranslate with engine.name as name and engine.number as number %}Hello {{ name }} #{{ number }}{% endblocktranslate %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate with name=engine.name number=engine.number %}Hello {{ name }} #{{ number }}{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_blocktranslate_multiple_with_mixed() {
+        let formatted = format(
+            "{% blocktranslate with engine.name as name and number=engine.number %}Hello {{ name }} #{{ number }}{% endblocktranslate %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate with name=engine.name number=engine.number %}Hello {{ name }} #{{ number }}{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_blocktranslate_with_filters() {
+        let formatted = format(
+            "{% blocktranslate with engine.name|upper as shouty %}HELLO {{ shouty }}{% endblocktranslate %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate with shouty=engine.name|upper %}HELLO {{ shouty }}{% endblocktranslate %}\n"
+        );
+    }
+
+    #[test]
+    fn test_migrate_assignments_blocktranslate_context() {
+        let formatted = format(
+            "{% blocktranslate with name=engine.name context 'greeting' %}Hello {{ name }}{% endblocktranslate %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% blocktranslate with name=engine.name context 'greeting' %}Hello {{ name }}{% endblocktranslate %}\n"
+        );
+    }
+
+    // Formatters
+
+    #[test]
+    fn test_dialect_formatters_in_order() {
+        for dialect in [Dialect::Django, Dialect::Jinja2] {
+            let mut all = Formatter::ALL.iter();
+            for formatter in dialect.formatters() {
+                assert!(
+                    all.any(|f| f == formatter),
+                    "{} out of order for {}",
+                    formatter.name(),
+                    dialect.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_formatter_quote_style_enabled() {
+        let mut options = FormatOptions::default();
+        assert!(!Formatter::QuoteStyle.is_enabled(&options));
+        assert!(Formatter::LoadTags.is_enabled(&options));
+        options.quote_style = Some(QuoteStyle::Double);
+        assert!(Formatter::QuoteStyle.is_enabled(&options));
+    }
+
+    #[test]
+    fn test_exclusions_left_untouched() {
+        let cases = [
+            (
+                Dialect::Django,
+                "{% verbatim %} ... {% endverbatim %} contents",
+                "{% verbatim %}{{a}}{%if  b%}{% endverbatim %}\n",
+            ),
+            (
+                Dialect::Django,
+                "{% comment %} ... {% endcomment %} contents",
+                "{% comment %}{{a}}{%if  b%}{% endcomment %}\n",
+            ),
+            (
+                Dialect::Jinja2,
+                "{% raw %} ... {% endraw %} contents",
+                "{% raw %}{{a}}{%if  b%}{% endraw %}\n",
+            ),
+            (
+                Dialect::Jinja2,
+                "{{ ... }} and {# ... #} tags with multi-line contents",
+                "{{ a\n  |b }}{# a\n  b #}\n",
+            ),
+        ];
+        for dialect in [Dialect::Django, Dialect::Jinja2] {
+            let described: Vec<&str> = cases
+                .iter()
+                .filter(|(d, _, _)| *d == dialect)
+                .map(|(_, exclusion, _)| *exclusion)
+                .collect();
+            assert_eq!(described, dialect.exclusions());
+        }
+        for (dialect, exclusion, content) in cases {
+            let options = FormatOptions {
+                dialect,
+                ..FormatOptions::default()
+            };
+            assert_eq!(
+                format_str(content, &options).unwrap(),
+                content,
+                "{}",
+                exclusion
+            );
+        }
+    }
+
+    // update_leading_trailing_whitespace
+
+    #[test]
+    fn test_update_leading_trailing_whitespace_added_newline_span() {
+        let content = "a\n{{ b }}";
+        let mut tokens = lex(content);
+        update_leading_trailing_whitespace(
+            &mut tokens,
+            "\n",
+            Span::empty_at(content, content.len()),
+        );
+        assert_eq!(spans(&tokens[2..]), vec![(9, 9, 2, 8)]);
+    }
+
+    #[test]
+    fn test_format_trim_leading_whitespace() {
+        let formatted = format("  \n  {% yolk %}\n", None);
+        assert_eq!(formatted, "  {% yolk %}\n");
+    }
+
+    #[test]
+    fn test_format_trim_trailing_whitespace() {
+        let formatted = format("{% yolk %}  \n  ", None);
+        assert_eq!(formatted, "{% yolk %}\n");
+    }
+
+    #[test]
+    fn test_format_trim_whitespace_mixed_crlf() {
+        let formatted = format(" \r\n {% yolk %}  \n  ", None);
+        assert_eq!(formatted, " {% yolk %}\r\n");
+    }
+
+    #[test]
+    fn test_format_preserve_content_whitespace() {
+        let formatted = format("{% block crack %}\n  Yum  \n{% endblock crack %}", None);
+        assert_eq!(
+            formatted,
+            "{% block crack %}\n  Yum  \n{% endblock crack %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_add_trailing_newline() {
+        let formatted = format("{% block crack %}Yum{% endblock %}", None);
+        assert_eq!(formatted, "{% block crack %}Yum{% endblock %}\n");
+    }
+
+    #[test]
+    fn test_format_whitespace_only_template() {
+        let formatted = format("  \t\n  ", None);
+        assert_eq!(formatted, "\n");
+    }
+
+    #[test]
+    fn test_format_whitespace_only_template_with_crlf() {
+        let formatted = format("  \t\r\n  ", None);
+        assert_eq!(formatted, "\r\n");
+    }
+
+    #[test]
+    fn test_format_no_text_tokens() {
+        let formatted = format("{% yolk %}", None);
+        assert_eq!(formatted, "{% yolk %}\n");
+    }
+
+    // update_if_expressions
+
+    #[test]
+    fn test_format_if_operators_split() {
+        let content = "{% if a==b and c>=1 %}{% endif %}\n";
+        assert_eq!(
+            format(content, None),
+            "{% if a == b and c >= 1 %}{% endif %}\n"
+        );
+        let diagnostics = diagnose_str(content, &FormatOptions::default()).unwrap();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec!["could not parse the remainder: '==b' from 'a==b' at line 1, column 1"]
+        );
+    }
+
+    #[test]
+    fn test_format_elif_operators_split() {
+        let formatted = format("{% if a %}{% elif b!=c|length %}{% endif %}\n", None);
+        assert_eq!(formatted, "{% if a %}{% elif b != c|length %}{% endif %}\n");
+    }
+
+    #[test]
+    fn test_format_if_operators_in_strings_unchanged() {
+        let formatted = format("{% if a == \"<b>\" %}\n", None);
+        assert_eq!(formatted, "{% if a == \"<b>\" %}\n");
+    }
+
+    #[test]
+    fn test_format_if_operators_invalid_unchanged() {
+        let content = "{% if a==b c %}{% endif %}\n";
+        assert_eq!(format(content, None), content);
+        let diagnostics = diagnose_str(content, &FormatOptions::default()).unwrap();
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec!["could not parse the remainder: '==b' from 'a==b' at line 1, column 1"]
+        );
+    }
+
+    #[test]
+    fn test_format_if_operators_other_tags_unchanged() {
+        let formatted = format("{% with a=b %}{% ifchanged a==b %}\n", None);
+        assert_eq!(formatted, "{% with a=b %}{% ifchanged a==b %}\n");
+    }
+
+    // update_quote_style
+
+    fn format_with_quote_style(content: &str, quote_style: QuoteStyle) -> String {
+        let options = FormatOptions {
+            quote_style: Some(quote_style),
+            ..FormatOptions::default()
+        };
+        format_str(content, &options).unwrap()
+    }
+
+    #[test]
+    fn test_format_quote_style_double() {
+        let formatted = format_with_quote_style(
+            "{% extends 'base.html' %}\n{% include 'a.html' with b='c' %}\n{{ d|default:'n/a' }}\n",
+            QuoteStyle::Double,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends \"base.html\" %}\n{% include \"a.html\" with b=\"c\" %}\n{{ d|default:\"n/a\" }}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_quote_style_single() {
+        let formatted = format_with_quote_style(
+            "{% url \"home\" as u %}{% translate \"Hi\" %}{% if a == \"b\" %}{% endif %}\n",
+            QuoteStyle::Single,
+        );
+        assert_eq!(
+            formatted,
+            "{% url 'home' as u %}{% translate 'Hi' %}{% if a == 'b' %}{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_quote_style_translated() {
+        let formatted = format_with_quote_style("{{ _('Hi')|upper }}\n", QuoteStyle::Double);
+        assert_eq!(formatted, "{{ _(\"Hi\")|upper }}\n");
+    }
+
+    #[test]
+    fn test_format_quote_style_containing_quote_unchanged() {
+        let content = "{% translate \"It's\" %}{{ a|default:\"\\'\" }}\n";
+        assert_eq!(
+            format_with_quote_style(content, QuoteStyle::Single),
+            content
+        );
+    }
+
+    #[test]
+    fn test_format_quote_style_escapes() {
+        let formatted =
+            format_with_quote_style("{{ a|default:'\\'b\\' \\\\ \\n' }}\n", QuoteStyle::Double);
+        assert_eq!(formatted, "{{ a|default:\"'b' \\\\ \\n\" }}\n");
+    }
+
+    #[test]
+    fn test_format_quote_style_other_tags_unchanged() {
+        let content = "{% load 'a' %}{% templatetag 'b' %}\n";
+        assert_eq!(
+            format_with_quote_style(content, QuoteStyle::Double),
+            content
+        );
+    }
+
+    #[test]
+    fn test_format_quote_style_unbalanced_quotes_unchanged() {
+        let content = "{% cycle a\" '' %}{% cycle ':| \"\" ) %}\n";
+        for quote_style in [QuoteStyle::Double, QuoteStyle::Single] {
+            assert_eq!(format_with_quote_style(content, quote_style), content);
+        }
+    }
+
+    #[test]
+    fn test_format_quote_style_idempotent() {
+        for content in [
+            "{% extends 'base.html' %}\n{% include \"a.html\" with b='c' %}\n",
+            "{{ a|default:'\\'b\\' \\\\ \\n' }}{{ _(\"Hi\")|upper }}\n",
+            "{% cycle a\" '' %}{% cycle '' \"b\" %}\n",
+            "{% if a == \"it's\" or b == 'c' %}{% endif %}\n",
+        ] {
+            for quote_style in [QuoteStyle::Double, QuoteStyle::Single] {
+                let formatted = format_with_quote_style(content, quote_style);
+                assert_eq!(
+                    format_with_quote_style(&formatted, quote_style),
+                    formatted,
+                    "{:?}",
+                    content
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_quote_style_unset() {
+        let content = "{% extends 'base.html' %}\n{{ a|default:\"b\" }}\n";
+        assert_eq!(format(content, None), content);
+    }
+
+    /// Django’s unescape_string_literal().
+    fn unescape_string_literal(s: &str) -> String {
+        let quote = &s[..1];
+        s[1..s.len() - 1]
+            .replace(&format!("\\{}", quote), quote)
+            .replace("\\\\", "\\")
+    }
+
+    #[test]
+    fn test_requote_constant_keeps_value() {
+        for value in [
+            r#""a""#,
+            r#""a\"b""#,
+            r#""a\\""#,
+            r#""\\\"""#,
+            r#""\\\\\"x""#,
+            r#""a\nb""#,
+            r#""\'""#,
+            r#"'a\'b'"#,
+            r#"'\\\''"#,
+            r#"'a"b'"#,
+        ] {
+            for quote in ['"', '\''] {
+                if let Some(requoted) = requote_constant(value, quote) {
+                    assert_eq!(
+                        unescape_string_literal(&requoted),
+                        unescape_string_literal(value),
+                        "{} requoted as {}",
+                        value,
+                        requoted,
+                    );
+                }
+            }
+        }
+    }
+
+    // update_load_tags
+
+    #[test]
+    fn test_update_load_tags_merged_span() {
+        let content = "{% load a %}\n{% load b %}\n{{ c }}";
+        let mut tokens = lex(content);
+        update_load_tags(&mut tokens);
+        assert_eq!(spans(&tokens[..1]), vec![(0, 25, 1, 1)]);
+        assert_eq!(spans(&tokens[1..]), vec![(25, 26, 2, 13), (26, 33, 3, 1)]);
+    }
+
+    #[test]
+    fn test_format_load_sorted() {
+        let formatted = format("{% load z y x %}\n", None);
+        assert_eq!(formatted, "{% load x y z %}\n");
+    }
+
+    #[test]
+    fn test_format_load_whitespace_cleaned() {
+        let formatted = format("{% load   x  y %}\n", None);
+        assert_eq!(formatted, "{% load x y %}\n");
+    }
+
+    #[test]
+    fn test_format_load_consecutive_merged() {
+        let formatted = format("{% load x %}{% load y %}\n", None);
+        assert_eq!(formatted, "{% load x y %}\n");
+    }
+
+    #[test]
+    fn test_format_load_consecutive_space_merged() {
+        let formatted = format("{% load x %} {% load y %}\n", None);
+        assert_eq!(formatted, "{% load x y %}\n");
+    }
+
+    #[test]
+    fn test_format_load_consecutive_newline_merged() {
+        let formatted = format("{% load x %}\n{% load y %}\n", None);
+        assert_eq!(formatted, "{% load x y %}\n");
+    }
+
+    #[test]
+    fn test_format_load_from_too_short_untouched() {
+        let formatted = format("{% load from a %}\n", None);
+        assert_eq!(formatted, "{% load from a %}\n");
+    }
+
+    #[test]
+    fn test_format_load_from_incorrect_untouched() {
+        let formatted = format("{% load c b from a thing %}\n", None);
+        assert_eq!(formatted, "{% load c b from a thing %}\n");
+    }
+
+    #[test]
+    fn test_format_load_from_sorted() {
+        let formatted = format("{% load c b from a %}\n", None);
+        assert_eq!(formatted, "{% load b c from a %}\n");
+    }
+
+    #[test]
+    fn test_format_load_from_unmerged_plain() {
+        let formatted = format("{% load b from a %}\n{% load c %}\n", None);
+        assert_eq!(formatted, "{% load b from a %}\n{% load c %}\n");
+    }
+
+    #[test]
+    fn test_format_load_plain_unmerged_from() {
+        let formatted = format("{% load c %}\n{% load b from a %}\n", None);
+        assert_eq!(formatted, "{% load c %}\n{% load b from a %}\n");
+    }
+
+    #[test]
+    fn test_format_load_from_unmerged_from() {
+        let formatted = format("{% load b from a %}\n{% load d from c %}\n", None);
+        assert_eq!(formatted, "{% load b from a %}\n{% load d from c %}\n");
+    }
+
+    #[test]
+    fn test_format_load_trailing_empty_lines_left() {
+        let formatted = format("{% load albumen %}\n\n{% albu %}\n", None);
+        assert_eq!(formatted, "{% load albumen %}\n\n{% albu %}\n");
+    }
+
+    // update_endblock_labels
+
+    #[test]
+    fn test_format_block_no_label() {
+        let formatted = format("{% block %}\n{% endblock %}\n", None);
+        assert_eq!(formatted, "{% block %}\n{% endblock %}\n");
+    }
+
+    #[test]
+    fn test_format_endblock_broken() {
+        let formatted = format("{% endblock %}\n", None);
+        assert_eq!(formatted, "{% endblock %}\n");
+    }
+
+    #[test]
+    fn test_format_endblock_broken_nesting() {
+        let formatted = format("{% block a %}\n{% endblock b %}\n", None);
+        assert_eq!(formatted, "{% block a %}\n{% endblock b %}\n");
+    }
+
+    #[test]
+    fn test_format_endblock_label_added_around_unclosed_tag() {
+        let formatted = format("{% block a %}\n{% if b %}\n{% endblock %}\n", None);
+        assert_eq!(formatted, "{% block a %}\n{% if b %}\n{% endblock a %}\n");
+    }
+
+    #[test]
+    fn test_format_endblock_label_added() {
+        let formatted = format("{% block h %}\n{% endblock %}\n", None);
+        assert_eq!(formatted, "{% block h %}\n{% endblock h %}\n");
+    }
+
+    #[test]
+    fn test_format_endblock_label_added_nested() {
+        let formatted = format(
+            "{% block h %}\n{% block i %}\n{% endblock %}\n{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% block h %}\n{% block i %}\n{% endblock i %}\n{% endblock h %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_endblock_label_removed() {
+        let formatted = format("{% block h %}i{% endblock h %}\n", None);
+        assert_eq!(formatted, "{% block h %}i{% endblock %}\n");
+    }
+
+    #[test]
+    fn test_format_endblock_with_blocktranslate() {
+        let formatted = format(
+            "{% block h %}\n{% blocktranslate %}ovo{% endblocktranslate %}\n{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% block h %}\n{% blocktranslate %}ovo{% endblocktranslate %}\n{% endblock h %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_partialdef_no_label() {
+        let formatted = format("{% partialdef %}\n{% endpartialdef %}\n", None);
+        assert_eq!(formatted, "{% partialdef %}\n{% endpartialdef %}\n");
+    }
+
+    #[test]
+    fn test_format_endpartialdef_broken() {
+        let formatted = format("{% endpartialdef %}\n", None);
+        assert_eq!(formatted, "{% endpartialdef %}\n");
+    }
+
+    #[test]
+    fn test_format_endpartialdef_broken_nesting() {
+        let formatted = format("{% partialdef a %}\n{% endpartialdef b %}\n", None);
+        assert_eq!(formatted, "{% partialdef a %}\n{% endpartialdef b %}\n");
+    }
+
+    #[test]
+    fn test_format_endpartialdef_label_added() {
+        let formatted = format("{% partialdef button %}\n{% endpartialdef %}\n", None);
+        assert_eq!(
+            formatted,
+            "{% partialdef button %}\n{% endpartialdef button %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_endpartialdef_label_added_nested() {
+        let formatted = format(
+            "{% partialdef a %}\n{% partialdef b %}\n{% endpartialdef %}\n{% endpartialdef %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% partialdef a %}\n{% partialdef b %}\n{% endpartialdef b %}\n{% endpartialdef a %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_endpartialdef_label_removed() {
+        let formatted = format("{% partialdef button %}i{% endpartialdef button %}\n", None);
+        assert_eq!(formatted, "{% partialdef button %}i{% endpartialdef %}\n");
+    }
+
+    #[test]
+    fn test_format_block_and_partialdef_independent_stacks() {
+        let formatted = format(
+            "{% block a %}\n{% partialdef b %}\n{% endpartialdef %}\n{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% block a %}\n{% partialdef b %}\n{% endpartialdef b %}\n{% endblock a %}\n"
+        );
+    }
+
+    fn format_with_paired_tags(content: &str) -> String {
+        let options = FormatOptions {
+            paired_tags: vec!["component".to_string(), "slot".to_string()],
+            ..FormatOptions::default()
+        };
+        format_str(content, &options).unwrap()
+    }
+
+    #[test]
+    fn test_format_paired_tag_label_added() {
+        let formatted = format_with_paired_tags(
+            "{% component \"card\" %}\n{% slot header %}\n{% endslot %}\n{% endcomponent %}\n",
+        );
+        assert_eq!(
+            formatted,
+            "{% component \"card\" %}\n{% slot header %}\n{% endslot header %}\n{% endcomponent \"card\" %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_paired_tag_label_removed() {
+        let formatted = format_with_paired_tags("{% slot header %}...{% endslot header %}\n");
+        assert_eq!(formatted, "{% slot header %}...{% endslot %}\n");
+    }
+
+    #[test]
+    fn test_format_paired_tag_no_label() {
+        let formatted = format_with_paired_tags("{% slot %}\n{% endslot %}\n");
+        assert_eq!(formatted, "{% slot %}\n{% endslot %}\n");
+    }
+
+    #[test]
+    fn test_format_paired_tag_not_configured() {
+        let formatted = format("{% slot header %}\n{% endslot %}\n", None);
+        assert_eq!(formatted, "{% slot header %}\n{% endslot %}\n");
+    }
+
+    #[test]
+    fn test_format_paired_tag_inside_block() {
+        let formatted =
+            format_with_paired_tags("{% block a %}\n{% slot b %}\n{% endslot %}\n{% endblock %}\n");
+        assert_eq!(
+            formatted,
+            "{% block a %}\n{% slot b %}\n{% endslot b %}\n{% endblock a %}\n"
+        );
+    }
+
+    // update_top_level_block_indentation
+
+    #[test]
+    fn test_format_extends_unindented() {
+        let formatted = format("  {% extends 'egg.html' %}\n", None);
+        assert_eq!(formatted, "{% extends 'egg.html' %}\n");
+    }
+
+    #[test]
+    fn test_format_top_level_blocks_unindented() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n\n  {% block yolk %}\n    yellow\n  {% endblock yolk %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n\n{% block yolk %}\n    yellow\n{% endblock yolk %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_top_level_blocks_trailing_spaces_unchanged() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n\n{% block classes %}{{ block.super }} yellow {% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n\n{% block classes %}{{ block.super }} yellow {% endblock %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_top_level_blocks_trailing_tabs_unchanged() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n\n{% block classes %}{{ block.super }}\tyellow\t{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n\n{% block classes %}{{ block.super }}\tyellow\t{% endblock %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_top_level_blocks_unindented_with_crlf() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\r\n\r\n  {% block yolk %}\r\n    yellow\r\n  {% endblock yolk %}\r\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\r\n\r\n{% block yolk %}\r\n    yellow\r\n{% endblock yolk %}\r\n"
+        );
+    }
+
+    #[test]
+    fn test_format_second_level_blocks_indented() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n\n{% block yolk %}\n  {% block white %}\n    protein\n  {% endblock white %}\n{% endblock yolk %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n\n{% block yolk %}\n  {% block white %}\n    protein\n  {% endblock white %}\n{% endblock yolk %}\n"
+        );
+    }
+
+    #[test]
+    fn test_no_unindent_without_extends() {
+        let formatted = format(
+            "  {% block yolk %}\n    yellow\n  {% endblock yolk %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "  {% block yolk %}\n    yellow\n  {% endblock yolk %}\n"
+        );
+    }
+
+    #[test]
+    fn test_unindent_multiple_blocks() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n\n  {% block yolk %}\n  yellow\n  {% endblock yolk %}\n\n  {% block white %}\n    protein\n  {% endblock white %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n\n{% block yolk %}\n  yellow\n{% endblock yolk %}\n\n{% block white %}\n    protein\n{% endblock white %}\n"
+        );
+    }
+
+    #[test]
+    fn test_no_unindenting_inside_if() {
+        let formatted = format(
+            "{% extends 'engine.html' %}\n{% if steam %}\n  {% block whistle %}\n  peep\n  {% endblock whistle %}\n{% endif %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'engine.html' %}\n{% if steam %}\n  {% block whistle %}\n  peep\n  {% endblock whistle %}\n{% endif %}\n"
+        );
+    }
+
+    #[test]
+    fn test_unindent_with_if_inside() {
+        let formatted = format(
+            "{% extends 'engine.html' %}\n\n  {% block whistle %}\n  {% if steam %}\n  peep\n  {% endif %}\n  {% endblock whistle %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'engine.html' %}\n\n{% block whistle %}\n  {% if steam %}\n  peep\n  {% endif %}\n{% endblock whistle %}\n"
+        );
+    }
+
+    // update_top_level_block_spacing
+
+    #[test]
+    fn test_update_top_level_block_spacing_no_change() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n\n{% block yolk %}Sunny side up{% endblock %}\n\n{% block white %}Albumin{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n\n{% block yolk %}Sunny side up{% endblock %}\n\n{% block white %}Albumin{% endblock %}\n"
+        );
+    }
+
+    #[test]
+    fn test_update_top_level_block_spacing_add_line() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n{% block yolk %}Sunny side up{% endblock %}\n{% block white %}Albumin{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n\n{% block yolk %}Sunny side up{% endblock %}\n\n{% block white %}Albumin{% endblock %}\n"
+        );
+    }
+
+    #[test]
+    fn test_update_top_level_block_spacing_add_line_with_crlf_first() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\r\n{% block yolk %}Sunny side up{% endblock %}\n{% block white %}Albumin{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\r\n\r\n{% block yolk %}Sunny side up{% endblock %}\r\n\r\n{% block white %}Albumin{% endblock %}\r\n"
+        );
+    }
+
+    #[test]
+    fn test_update_top_level_block_spacing_remove_extra_lines() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n\n\n{% block yolk %}Sunny side up{% endblock %}\n\n\n{% block white %}Albumin{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n\n{% block yolk %}Sunny side up{% endblock %}\n\n{% block white %}Albumin{% endblock %}\n"
+        );
+    }
+
+    #[test]
+    fn test_update_top_level_block_spacing_remove_extra_line_with_crlf_first() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\r\n\r\n\r\n{% block yolk %}Sunny side up{% endblock %}\n\n\n{% block white %}Albumin{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\r\n\r\n{% block yolk %}Sunny side up{% endblock %}\r\n\r\n{% block white %}Albumin{% endblock %}\r\n"
+        );
+    }
+
+    #[test]
+    fn test_update_top_level_block_spacing_nested_blocks() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n\n{% block yolk %}{% block inner_yolk %}Runny{% endblock %}{% endblock %}\n\n{% block white %}Firm{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n\n{% block yolk %}{% block inner_yolk %}Runny{% endblock %}{% endblock %}\n\n{% block white %}Firm{% endblock %}\n"
+        );
+    }
+
+    #[test]
+    fn test_update_top_level_block_spacing_adjacent_to_extends() {
+        let formatted = format(
+            "{% extends 'egg.html' %}{% block yolk %}Runny{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}{% block yolk %}Runny{% endblock %}\n"
+        );
+    }
+
+    #[test]
+    fn test_update_top_level_block_spacing_other_block_tags() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n{% block yolk %}{% if runny %}Runny{% endif %}{% endblock %}\n{% block white %}Firm{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n\n{% block yolk %}{% if runny %}Runny{% endif %}{% endblock %}\n\n{% block white %}Firm{% endblock %}\n"
+        );
+    }
+
+    #[test]
+    fn test_update_top_level_block_spacing_no_extends() {
+        let formatted = format(
+            "{% block yolk %}Sunny side up{% endblock %}\n{% block white %}Albumin{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% block yolk %}Sunny side up{% endblock %}\n{% block white %}Albumin{% endblock %}\n"
+        );
+    }
+
+    #[test]
+    fn test_update_top_level_block_spacing_content() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n\n(not rendered)\n\n{% block yolk %}Sunny side up{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n\n(not rendered)\n\n{% block yolk %}Sunny side up{% endblock %}\n"
+        );
+    }
+
+    #[test]
+    fn test_update_top_level_block_spacing_comment() {
+        let formatted = format(
+            "{% extends 'egg.html' %}\n{# bla #}\n{% block yolk %}Sunny side up{% endblock %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% extends 'egg.html' %}\n{# bla #}\n{% block yolk %}Sunny side up{% endblock %}\n"
+        );
+    }
+
+    // Final build
+
+    #[test]
+    fn test_format_spaces_added() {
+        let formatted = format("a {{var}} {%tag%} {#comment#}\n", None);
+        assert_eq!(formatted, "a {{ var }} {% tag %} {# comment #}\n");
+    }
+
+    #[test]
+    fn test_format_spaces_removed() {
+        let formatted = format("a {{  var  }} {%  tag  %} {#  comment  #}\n", None);
+        assert_eq!(formatted, "a {{ var }} {% tag %} {# comment #}\n");
+    }
+
+    #[test]
+    fn test_format_verbatim_left() {
+        let formatted = format(
+            "a {% verbatim %} {{var}} {%tag%} {#comment#} {% endverbatim %}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "a {% verbatim %} {{var}} {%tag%} {#comment#} {% endverbatim %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_comment_block_left() {
+        let formatted = format(
+            "a {%comment%} {{var}} {%tag%} {#comment#} {%endcomment%}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "a {% comment %} {{var}} {%tag%} {#comment#} {% endcomment %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_comment_block_with_note_left() {
+        let formatted = format(
+            "{% comment \"Old\" %}\n{% load i18n %}{% load a %}\n{% trans 'Hi' %}\n{% endcomment %}\n",
+            Some((5, 2)),
+        );
+        assert_eq!(
+            formatted,
+            "{% comment \"Old\" %}\n{% load i18n %}{% load a %}\n{% trans 'Hi' %}\n{% endcomment %}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_comment_block_not_nested() {
+        let formatted = format(
+            "{% comment %}{% comment %}{{a}}{% endcomment %}{{b}}\n",
+            None,
+        );
+        assert_eq!(
+            formatted,
+            "{% comment %}{% comment %}{{a}}{% endcomment %}{{ b }}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_commented_tag_prefix_not_comment_block() {
+        let formatted = format("{% commentary %}{{a}}\n", None);
+        assert_eq!(formatted, "{% commentary %}{{ a }}\n");
+    }
+
+    // format_variables
+
+    #[test]
+    fn test_format_variables_constant_int() {
+        let formatted = format("{{ 1 }}\n", None);
+        assert_eq!(formatted, "{{ 1 }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_constant_float() {
+        let formatted = format("{{ 1.23 }}\n", None);
+        assert_eq!(formatted, "{{ 1.23 }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_constant_float_negative() {
+        let formatted = format("{{ -1.23 }}\n", None);
+        assert_eq!(formatted, "{{ -1.23 }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_constant_str() {
+        let formatted = format("{{ 'egg' }}\n", None);
+        assert_eq!(formatted, "{{ 'egg' }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_constant_str_translated() {
+        let formatted = format("{{ _('egg') }}\n", None);
+        assert_eq!(formatted, "{{ _('egg') }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_var() {
+        let formatted = format("{{ egg }}\n", None);
+        assert_eq!(formatted, "{{ egg }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_var_attr() {
+        let formatted = format("{{ egg.shell }}\n", None);
+        assert_eq!(formatted, "{{ egg.shell }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_variable_filter_no_arg() {
+        let formatted = format("{{ egg | crack }}\n", None);
+        assert_eq!(formatted, "{{ egg|crack }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_variable_filter_constant_arg() {
+        let formatted = format("{{ egg | crack:'fully' }}\n", None);
+        assert_eq!(formatted, "{{ egg|crack:'fully' }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_variable_filter_variable_arg() {
+        let formatted = format("{{ egg | crack:amount }}\n", None);
+        assert_eq!(formatted, "{{ egg|crack:amount }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_syntax_error_start() {
+        let formatted = format("{{ ?egg | crack }}\n", None);
+        assert_eq!(formatted, "{{ ?egg | crack }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_syntax_error_end() {
+        let formatted = format("{{ egg | crack? }}\n", None);
+        assert_eq!(formatted, "{{ egg | crack? }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_syntax_error_middle() {
+        let formatted = format("{{ engines[0].name | length }}\n", None);
+        assert_eq!(formatted, "{{ engines[0].name | length }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_filter_without_variable() {
+        let formatted = format("{{ |crack|boil }}\n", None);
+        assert_eq!(formatted, "{{ |crack|boil }}\n");
+    }
+
+    #[test]
+    fn test_format_variables_double_dot() {
+        // Double dot syntax for "empty string lookup" deprecated:
+        // https://code.djangoproject.com/ticket/35738
+        let formatted = format("{{ engine..name }}\n", None);
+        assert_eq!(formatted, "{{ engine..name }}\n");
+    }
+
+    #[test]
+    fn test_format_block_bits() {
+        let formatted = format("{%  if breakfast  ==  'egg'  %}\n", None);
+        assert_eq!(formatted, "{% if breakfast == 'egg' %}\n");
+    }
+
+    #[test]
+    fn test_format_block_bits_spaces_in_string() {
+        let formatted = format("{% if breakfast == 'egg  mcmuffin' %}\n", None);
+        assert_eq!(formatted, "{% if breakfast == 'egg  mcmuffin' %}\n");
+    }
+
+    #[test]
+    fn test_format_block_bits_spaces_in_translated_string() {
+        let formatted = format("{% if breakfast == _('egg  mcmuffin') %}\n", None);
+        assert_eq!(formatted, "{% if breakfast == _('egg  mcmuffin') %}\n");
+    }
+}
@@ -0,0 +1,190 @@
+// Python extension module, built by maturin with the “python” feature.
+
+use crate::{Dialect, DjadeError, FormatOptions, Version, check_str, format_str};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+fn format_options(target_version: Option<&str>) -> PyResult<FormatOptions> {
+    let target_version = target_version
+        .map(|version_str| {
+            let version = version_str.parse::<Version>().map_err(|e| {
+                PyValueError::new_err(format!("Invalid target version {version_str:?}: {e}"))
+            })?;
+            if !version.is_supported() {
+                return Err(value_error(DjadeError::UnsupportedVersion(version)));
+            }
+            Ok(version)
+        })
+        .transpose()?;
+    Ok(FormatOptions {
+        target_version,
+        ..FormatOptions::default()
+    })
+}
+
+fn value_error(error: DjadeError) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+/// Format a template string.
+#[pyfunction]
+#[pyo3(signature = (text, target_version=None))]
+fn format_string(text: &str, target_version: Option<&str>) -> PyResult<String> {
+    let options = format_options(target_version)?;
+    format_str(text, &options).map_err(value_error)
+}
+
+/// Format many template strings in parallel, without holding the GIL.
+#[pyfunction]
+#[pyo3(signature = (texts, target_version=None))]
+fn format_strings(
+    py: Python<'_>,
+    texts: Vec<String>,
+    target_version: Option<&str>,
+) -> PyResult<Vec<String>> {
+    let options = format_options(target_version)?;
+    let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    py.detach(|| format_parallel(&texts, &options, threads))
+        .map_err(value_error)
+}
+
+/// Return whether a template file is already formatted.
+#[pyfunction]
+#[pyo3(signature = (path, target_version=None))]
+fn check(py: Python<'_>, path: PathBuf, target_version: Option<&str>) -> PyResult<bool> {
+    let options = FormatOptions {
+        dialect: Dialect::from_path(&path),
+        ..format_options(target_version)?
+    };
+    let content = py.detach(|| std::fs::read_to_string(&path))?;
+    check_str(&content, &options).map_err(value_error)
+}
+
+/// Run the command line interface with `sys.argv`, returning the exit code.
+#[pyfunction]
+fn main(py: Python<'_>) -> PyResult<i32> {
+    let argv: Vec<String> = py.import("sys")?.getattr("argv")?.extract()?;
+    Ok(py.detach(|| crate::cli::run(argv)))
+}
+
+/// Format `texts` split into a chunk per thread, keeping their order.
+fn format_parallel(
+    texts: &[String],
+    options: &FormatOptions,
+    threads: usize,
+) -> Result<Vec<String>, DjadeError> {
+    let chunk_size = texts.len().div_ceil(threads).max(1);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = texts
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|text| format_str(text, options))
+                        .collect::<Result<Vec<_>, _>>()
+                })
+            })
+            .collect();
+
+        let mut formatted = Vec::with_capacity(texts.len());
+        for handle in handles {
+            formatted.extend(handle.join().unwrap()?);
+        }
+        Ok(formatted)
+    })
+}
+
+#[pymodule]
+fn _djade(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(format_string, m)?)?;
+    m.add_function(wrap_pyfunction!(format_strings, m)?)?;
+    m.add_function(wrap_pyfunction!(check, m)?)?;
+    m.add_function(wrap_pyfunction!(main, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // format_options
+
+    #[test]
+    fn test_format_options_target_version() {
+        let options = format_options(Some("4.2")).unwrap();
+        assert_eq!(options.target_version, Some(Version::new(4, 2)));
+    }
+
+    #[test]
+    fn test_format_options_unsupported_target_version() {
+        Python::initialize();
+        Python::attach(|py| {
+            let error = format_options(Some("9.9")).unwrap_err();
+            assert_eq!(
+                error.value(py).to_string(),
+                DjadeError::UnsupportedVersion(Version::new(9, 9)).to_string()
+            );
+        });
+    }
+
+    #[test]
+    fn test_format_options_invalid_target_version() {
+        assert!(format_options(Some("four")).is_err());
+    }
+
+    // check
+
+    #[test]
+    fn test_check_jinja2() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = "{%- if f(a, b) %}x{% endif -%}\n";
+        let jinja_path = dir.path().join("engine.jinja");
+        std::fs::write(&jinja_path, content).unwrap();
+        let django_path = dir.path().join("engine.html");
+        std::fs::write(&django_path, content).unwrap();
+
+        Python::initialize();
+        Python::attach(|py| {
+            assert!(check(py, jinja_path, None).unwrap());
+            assert!(!check(py, django_path, None).unwrap());
+        });
+    }
+
+    // format_parallel
+
+    #[test]
+    fn test_format_parallel_keeps_order() {
+        let texts: Vec<String> = (0..10).map(|i| format!("{{{{e{}}}}}", i)).collect();
+        let expected: Vec<String> = (0..10).map(|i| format!("{{{{ e{} }}}}\n", i)).collect();
+        for threads in [1, 3, 4, 16] {
+            let formatted = format_parallel(&texts, &FormatOptions::default(), threads).unwrap();
+            assert_eq!(formatted, expected, "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn test_format_parallel_first_error() {
+        let options = FormatOptions {
+            dialect: Dialect::Jinja2,
+            ..FormatOptions::default()
+        };
+        let mut texts = vec!["{{ a }}".to_string(); 10];
+        texts[9] = "{{ b".to_string();
+        texts[2] = "a\n{% c".to_string();
+        // The errors are in the first and last of four chunks.
+        let error = format_parallel(&texts, &options, 4).unwrap_err();
+        assert_eq!(error.to_string(), "unclosed block tag at line 2, column 1");
+    }
+
+    #[test]
+    fn test_format_parallel_empty() {
+        assert_eq!(
+            format_parallel(&[], &FormatOptions::default(), 4).unwrap(),
+            Vec::<String>::new()
+        );
+    }
+}
@@ -0,0 +1,391 @@
+// Parser for the expressions of {% if %} and {% elif %} tags, based on
+// Django’s:
+// https://github.com/django/django/blob/main/django/template/smartif.py
+
+use crate::filter_expression_error;
+use std::borrow::Cow;
+
+/// Operators with their binding power. `not` is the only prefix operator.
+const OPERATORS: &[(&str, u8)] = &[
+    ("or", 6),
+    ("and", 7),
+    ("not", 8),
+    ("in", 9),
+    ("not in", 9),
+    ("is", 10),
+    ("is not", 10),
+    ("==", 10),
+    ("!=", 10),
+    (">", 10),
+    (">=", 10),
+    ("<", 10),
+    ("<=", 10),
+];
+
+/// Comparison operators that can be glued to their operands, like `a==b`,
+/// longest first.
+const SYMBOLS: &[&str] = &["==", "!=", ">=", "<=", ">", "<"];
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Expr<'b> {
+    Literal(Cow<'b, str>),
+    Not(Box<Expr<'b>>),
+    Infix {
+        operator: &'static str,
+        left: Box<Expr<'b>>,
+        right: Box<Expr<'b>>,
+    },
+}
+
+impl<'b> Expr<'b> {
+    /// The expression as tag bits, with each operator a separate bit.
+    pub(crate) fn bits(self) -> Vec<Cow<'b, str>> {
+        let mut bits = Vec::new();
+        self.push_bits(&mut bits);
+        bits
+    }
+
+    fn push_bits(self, bits: &mut Vec<Cow<'b, str>>) {
+        match self {
+            Expr::Literal(text) => bits.push(text),
+            Expr::Not(operand) => {
+                bits.push(Cow::Borrowed("not"));
+                operand.push_bits(bits);
+            }
+            Expr::Infix {
+                operator,
+                left,
+                right,
+            } => {
+                left.push_bits(bits);
+                bits.extend(operator.split(' ').map(Cow::Borrowed));
+                right.push_bits(bits);
+            }
+        }
+    }
+
+    /// Replace literals used as conditions, rather than compared with another
+    /// value, with the expressions `replace` returns.
+    pub(crate) fn replace_conditions(
+        &mut self,
+        replace: &mut impl FnMut(&str) -> Option<Expr<'b>>,
+    ) -> bool {
+        match self {
+            Expr::Literal(text) => match replace(text) {
+                Some(expr) => {
+                    *self = expr;
+                    true
+                }
+                None => false,
+            },
+            Expr::Not(operand) => operand.replace_conditions(replace),
+            Expr::Infix {
+                operator: "and" | "or",
+                left,
+                right,
+            } => left.replace_conditions(replace) | right.replace_conditions(replace),
+            Expr::Infix { .. } => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Item<'b> {
+    Operator(&'static str, u8),
+    Literal(Cow<'b, str>),
+    End,
+}
+
+impl Item<'_> {
+    fn binding_power(&self) -> u8 {
+        match self {
+            Item::Operator(_, binding_power) => *binding_power,
+            Item::Literal(_) | Item::End => 0,
+        }
+    }
+
+    fn display(&self) -> &str {
+        match self {
+            Item::Operator(operator, _) => operator,
+            Item::Literal(text) => text,
+            Item::End => "",
+        }
+    }
+}
+
+/// Parse the bits of an if tag after its name, returning Django’s error
+/// message for invalid expressions.
+pub(crate) fn parse<'b>(bits: &[Cow<'b, str>]) -> Result<Expr<'b>, String> {
+    let mut items = Vec::with_capacity(bits.len() + 1);
+    let mut i = 0;
+    while i < bits.len() {
+        let bit = bits[i].as_ref();
+        let next = bits.get(i + 1).map(AsRef::as_ref);
+        let name = match (bit, next) {
+            ("is", Some("not")) => "is not",
+            ("not", Some("in")) => "not in",
+            _ => bit,
+        };
+        if name != bit {
+            i += 1;
+        }
+        match OPERATORS.iter().find(|(operator, _)| *operator == name) {
+            Some((operator, binding_power)) => items.push(Item::Operator(operator, *binding_power)),
+            None => {
+                if let Some(message) = filter_expression_error(bit) {
+                    return Err(message);
+                }
+                items.push(Item::Literal(bits[i].clone()));
+            }
+        }
+        i += 1;
+    }
+    items.push(Item::End);
+
+    let mut parser = Parser { items, position: 0 };
+    let expr = parser.expression(0)?;
+    match &parser.items[parser.position] {
+        Item::End => Ok(expr),
+        item => Err(format!(
+            "unused '{}' at end of if expression",
+            item.display()
+        )),
+    }
+}
+
+struct Parser<'b> {
+    items: Vec<Item<'b>>,
+    position: usize,
+}
+
+impl<'b> Parser<'b> {
+    fn binding_power(&self) -> u8 {
+        self.items[self.position].binding_power()
+    }
+
+    fn next(&mut self) -> Item<'b> {
+        let item = self.items[self.position].clone();
+        if !matches!(item, Item::End) {
+            self.position += 1;
+        }
+        item
+    }
+
+    fn expression(&mut self, right_binding_power: u8) -> Result<Expr<'b>, String> {
+        let mut left = match self.next() {
+            Item::Literal(text) => Expr::Literal(text),
+            Item::Operator("not", binding_power) => {
+                Expr::Not(Box::new(self.expression(binding_power)?))
+            }
+            Item::Operator(operator, _) => {
+                return Err(format!(
+                    "not expecting '{}' in this position in if tag",
+                    operator
+                ));
+            }
+            Item::End => return Err("unexpected end of expression in if tag".to_string()),
+        };
+        while right_binding_power < self.binding_power() {
+            left = match self.next() {
+                Item::Operator("not", _) => {
+                    return Err("not expecting 'not' as infix operator in if tag".to_string());
+                }
+                Item::Operator(operator, binding_power) => Expr::Infix {
+                    operator,
+                    left: Box::new(left),
+                    right: Box::new(self.expression(binding_power)?),
+                },
+                Item::Literal(_) | Item::End => unreachable!(),
+            };
+        }
+        Ok(left)
+    }
+}
+
+/// Split comparison operators glued to their operands, like `a==b`, into
+/// separate bits, outside of quoted strings.
+pub(crate) fn split_operators<'a>(bits: &[Cow<'a, str>]) -> Vec<Cow<'a, str>> {
+    let mut split = Vec::with_capacity(bits.len());
+    for bit in bits {
+        if SYMBOLS.contains(&bit.as_ref()) {
+            split.push(bit.clone());
+            continue;
+        }
+        let mut start = 0;
+        let mut quote = None;
+        let mut i = 0;
+        let bytes = bit.as_bytes();
+        while i < bytes.len() {
+            match (quote, bytes[i]) {
+                (Some(_), b'\\') => i += 1,
+                (Some(q), b) if b == q => quote = None,
+                (None, b @ (b'"' | b'\'')) => quote = Some(b),
+                (None, b'=' | b'!' | b'<' | b'>') => {
+                    if let Some(symbol) = SYMBOLS.iter().find(|s| bit[i..].starts_with(*s)) {
+                        if i > start {
+                            split.push(Cow::Owned(bit[start..i].to_string()));
+                        }
+                        split.push(Cow::Borrowed(*symbol));
+                        i += symbol.len();
+                        start = i;
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        if start == 0 {
+            split.push(bit.clone());
+        } else if start < bytes.len() {
+            split.push(Cow::Owned(bit[start..].to_string()));
+        }
+    }
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(text: &str) -> Vec<String> {
+        split_operators(&bits(text))
+            .into_iter()
+            .map(Cow::into_owned)
+            .collect()
+    }
+
+    fn literal(text: &str) -> Box<Expr<'_>> {
+        Box::new(Expr::Literal(Cow::Borrowed(text)))
+    }
+
+    fn bits(text: &str) -> Vec<Cow<'_, str>> {
+        text.split_whitespace().map(Cow::Borrowed).collect()
+    }
+
+    fn error(text: &str) -> String {
+        parse(&bits(text)).unwrap_err()
+    }
+
+    #[test]
+    fn test_split_operators() {
+        assert_eq!(split("a==b"), vec!["a", "==", "b"]);
+        assert_eq!(split("a<=b>c"), vec!["a", "<=", "b", ">", "c"]);
+        assert_eq!(split("a!=1 or b"), vec!["a", "!=", "1", "or", "b"]);
+        assert_eq!(split(">=x"), vec![">=", "x"]);
+        assert_eq!(split("x<"), vec!["x", "<"]);
+    }
+
+    #[test]
+    fn test_split_operators_unchanged() {
+        assert_eq!(split("a == b"), vec!["a", "==", "b"]);
+        assert_eq!(split("a=b"), vec!["a=b"]);
+        assert_eq!(split("not(x)"), vec!["not(x)"]);
+    }
+
+    #[test]
+    fn test_split_operators_quoted() {
+        assert_eq!(split(r#"a=="<>""#), vec!["a", "==", r#""<>""#]);
+        assert_eq!(split(r#"a|default:'=\'>'"#), vec![r#"a|default:'=\'>'"#]);
+    }
+
+    #[test]
+    fn test_parse_precedence() {
+        let bits = bits("a or b and not c == d");
+        let expr = parse(&bits).unwrap();
+        assert_eq!(
+            expr,
+            Expr::Infix {
+                operator: "or",
+                left: literal("a"),
+                right: Box::new(Expr::Infix {
+                    operator: "and",
+                    left: literal("b"),
+                    right: Box::new(Expr::Not(Box::new(Expr::Infix {
+                        operator: "==",
+                        left: literal("c"),
+                        right: literal("d"),
+                    }))),
+                }),
+            }
+        );
+        assert_eq!(expr.bits(), bits);
+    }
+
+    #[test]
+    fn test_parse_two_word_operators() {
+        let bits = bits("a not in b and c is not None");
+        let expr = parse(&bits).unwrap();
+        assert!(matches!(
+            expr,
+            Expr::Infix {
+                operator: "and",
+                ..
+            }
+        ));
+        assert_eq!(expr.bits(), bits);
+    }
+
+    #[test]
+    fn test_parse_filters_and_constants() {
+        let mut bits = bits("a|length > 1 or _ in d");
+        bits[4] = Cow::Borrowed(r#"_("b c")"#);
+        assert_eq!(parse(&bits).unwrap().bits(), bits);
+    }
+
+    #[test]
+    fn test_replace_conditions() {
+        let mut expr = parse(&bits("a and not b or c == d or e in f")).unwrap();
+        let mut seen = Vec::new();
+        let replaced = expr.replace_conditions(&mut |text| {
+            seen.push(text.to_string());
+            (text == "b").then(|| Expr::Infix {
+                operator: "is",
+                left: literal("x"),
+                right: literal("y"),
+            })
+        });
+        assert!(replaced);
+        assert_eq!(seen, vec!["a", "b"]);
+        assert_eq!(expr.bits(), bits("a and not x is y or c == d or e in f"));
+    }
+
+    #[test]
+    fn test_parse_error_empty() {
+        assert_eq!(error(""), "unexpected end of expression in if tag");
+    }
+
+    #[test]
+    fn test_parse_error_unexpected_end() {
+        assert_eq!(error("a =="), "unexpected end of expression in if tag");
+    }
+
+    #[test]
+    fn test_parse_error_operator_position() {
+        assert_eq!(
+            error("and a"),
+            "not expecting 'and' in this position in if tag"
+        );
+    }
+
+    #[test]
+    fn test_parse_error_infix_not() {
+        assert_eq!(
+            error("a not b"),
+            "not expecting 'not' as infix operator in if tag"
+        );
+    }
+
+    #[test]
+    fn test_parse_error_unused() {
+        assert_eq!(error("a b"), "unused 'b' at end of if expression");
+    }
+
+    #[test]
+    fn test_parse_error_unparsed_literal() {
+        assert_eq!(
+            error("not(x)"),
+            "could not parse the remainder: '(x)' from 'not(x)'"
+        );
+    }
+}
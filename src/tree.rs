@@ -0,0 +1,589 @@
+// Nesting of paired block tags, built over the flat token list.
+//
+// Nodes refer to tokens by index, so rules can modify the token list whilst
+// walking the tree. A tree is invalidated by inserting or removing tokens.
+
+use crate::{Diagnostic, Token};
+use serde::Serialize;
+use std::borrow::Cow;
+
+/// Tags that open a block, with their intermediate tags. Each closes with a
+/// tag named `end` + its name.
+const PAIRED_TAGS: &[(&str, &[&str])] = &[
+    ("autoescape", &[]),
+    ("block", &[]),
+    ("blocktrans", &["plural"]),
+    ("blocktranslate", &["plural"]),
+    ("cache", &[]),
+    ("comment", &[]),
+    ("filter", &[]),
+    ("for", &["empty"]),
+    ("if", &["elif", "else"]),
+    ("ifchanged", &["else"]),
+    ("ifequal", &["else"]),
+    ("ifnotequal", &["else"]),
+    ("language", &[]),
+    ("localize", &[]),
+    ("localtime", &[]),
+    ("partialdef", &[]),
+    ("spaceless", &[]),
+    ("timezone", &[]),
+    ("verbatim", &[]),
+    ("with", &[]),
+];
+
+/// Jinja2’s paired tags, for the same. `set` only opens a block when it has
+/// no `=`, as in `{% set x %}` … `{% endset %}`.
+const JINJA2_PAIRED_TAGS: &[(&str, &[&str])] = &[
+    ("autoescape", &[]),
+    ("block", &[]),
+    ("call", &[]),
+    ("filter", &[]),
+    ("for", &["else"]),
+    ("if", &["elif", "else"]),
+    ("macro", &[]),
+    ("raw", &[]),
+    ("set", &[]),
+    ("trans", &["pluralize"]),
+    ("with", &[]),
+];
+
+/// Every intermediate tag in PAIRED_TAGS, which configured paired tags
+/// accept too, as Djade can’t know which they use.
+const INTERMEDIATE_TAGS: &[&str] = &["elif", "else", "empty", "plural"];
+
+/// Every intermediate tag in JINJA2_PAIRED_TAGS, for the same.
+const JINJA2_INTERMEDIATE_TAGS: &[&str] = &["elif", "else", "pluralize"];
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Node {
+    /// A token that isn’t part of a paired tag structure.
+    Leaf(usize),
+    Block(Block),
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Block {
+    /// The opening tag and each intermediate tag, like `elif` and `else`,
+    /// each with the nodes that follow it.
+    pub(crate) branches: Vec<Branch>,
+    /// The closing tag, or `None` if the block is unclosed.
+    pub(crate) end: Option<usize>,
+}
+
+impl Block {
+    pub(crate) fn start(&self) -> usize {
+        self.branches[0].tag
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Branch {
+    pub(crate) tag: usize,
+    pub(crate) children: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub(crate) struct Position {
+    /// The number of blocks enclosing the token. A block’s own tags are at
+    /// the depth of the block, and its contents one deeper.
+    pub(crate) depth: usize,
+    /// The opening tag of the innermost enclosing block.
+    pub(crate) parent: Option<usize>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Tree {
+    pub(crate) root: Vec<Node>,
+    /// The position of each token, by index.
+    pub(crate) positions: Vec<Position>,
+    /// Unexpected, mismatched, and unclosed tags, in template order.
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+struct OpenBlock<'t> {
+    name: &'t str,
+    intermediates: &'static [&'static str],
+    block: Block,
+}
+
+impl Tree {
+    pub(crate) fn parse(tokens: &[Token<'_>]) -> Tree {
+        Tree::parse_with_paired_tags(tokens, &[])
+    }
+
+    /// Parse with extra paired tags, which accept any built-in intermediate
+    /// tag.
+    pub(crate) fn parse_with_paired_tags(tokens: &[Token<'_>], paired_tags: &[String]) -> Tree {
+        Tree::parse_with_table(tokens, PAIRED_TAGS, INTERMEDIATE_TAGS, paired_tags)
+    }
+
+    /// Parse a Jinja2 template, with extra paired tags.
+    pub(crate) fn parse_jinja2(tokens: &[Token<'_>], paired_tags: &[String]) -> Tree {
+        Tree::parse_with_table(
+            tokens,
+            JINJA2_PAIRED_TAGS,
+            JINJA2_INTERMEDIATE_TAGS,
+            paired_tags,
+        )
+    }
+
+    fn parse_with_table(
+        tokens: &[Token<'_>],
+        table: &'static [(&'static str, &'static [&'static str])],
+        intermediate_tags: &'static [&'static str],
+        paired_tags: &[String],
+    ) -> Tree {
+        let mut root = Vec::new();
+        let mut positions = Vec::with_capacity(tokens.len());
+        let mut diagnostics = Vec::new();
+        let mut stack: Vec<OpenBlock> = Vec::new();
+
+        for (i, token) in tokens.iter().enumerate() {
+            let depth = stack.len();
+            let parent = stack.last().map(|open| open.block.start());
+            let (name, args) = match token {
+                Token::Block { bits, .. } => (bits[0].as_ref(), &bits[1..]),
+                _ => ("", &[][..]),
+            };
+            let intermediates = match table.iter().find(|(tag, _)| *tag == name) {
+                Some(("set", _)) if is_assignment(args) => None,
+                Some((_, intermediates)) => Some(*intermediates),
+                None if paired_tags.iter().any(|tag| tag == name) => Some(intermediate_tags),
+                None => None,
+            };
+            if let Some(intermediates) = intermediates {
+                positions.push(Position { depth, parent });
+                stack.push(OpenBlock {
+                    name,
+                    intermediates,
+                    block: Block {
+                        branches: vec![Branch {
+                            tag: i,
+                            children: Vec::new(),
+                        }],
+                        end: None,
+                    },
+                });
+                continue;
+            }
+
+            if let Some(open) = stack.last_mut()
+                && open.intermediates.contains(&name)
+            {
+                positions.push(Position {
+                    depth: depth - 1,
+                    parent: parent_of(&open.block, &positions),
+                });
+                open.block.branches.push(Branch {
+                    tag: i,
+                    children: Vec::new(),
+                });
+                continue;
+            }
+
+            if let Some(opened) = name.strip_prefix("end")
+                && let Some(index) = stack.iter().rposition(|open| open.name == opened)
+            {
+                if index < depth - 1 {
+                    diagnostics.push(Diagnostic {
+                        message: format!(
+                            "expected 'end{}', found '{}'",
+                            stack[depth - 1].name,
+                            name
+                        ),
+                        location: token.span().location,
+                    });
+                }
+                // Blocks opened inside the matched one are left unclosed.
+                while stack.len() > index + 1 {
+                    let open = stack.pop().unwrap();
+                    push_node(&mut stack, &mut root, Node::Block(open.block));
+                }
+                let mut open = stack.pop().unwrap();
+                positions.push(Position {
+                    depth: index,
+                    parent: parent_of(&open.block, &positions),
+                });
+                open.block.end = Some(i);
+                push_node(&mut stack, &mut root, Node::Block(open.block));
+                continue;
+            }
+
+            // Intermediate tags outside their block aren’t reported, since
+            // unrecognized tags, like third-party ones, may consume them.
+            if is_paired_tag_end(name, table, paired_tags) {
+                diagnostics.push(Diagnostic {
+                    message: format!("unexpected '{}'", name),
+                    location: token.span().location,
+                });
+            }
+            positions.push(Position { depth, parent });
+            push_node(&mut stack, &mut root, Node::Leaf(i));
+        }
+
+        for open in &stack {
+            diagnostics.push(Diagnostic {
+                message: format!("unclosed '{}'", open.name),
+                location: tokens[open.block.start()].span().location,
+            });
+        }
+        while let Some(open) = stack.pop() {
+            push_node(&mut stack, &mut root, Node::Block(open.block));
+        }
+        diagnostics
+            .sort_by_key(|diagnostic| (diagnostic.location.line, diagnostic.location.column));
+
+        Tree {
+            root,
+            positions,
+            diagnostics,
+        }
+    }
+
+    /// All blocks in the tree, outermost first.
+    pub(crate) fn blocks(&self) -> Vec<&Block> {
+        let mut blocks = Vec::new();
+        collect_blocks(&self.root, &mut blocks);
+        blocks
+    }
+}
+
+/// Whether a tag is the closing tag of a paired tag.
+fn is_paired_tag_end(name: &str, table: &[(&str, &[&str])], paired_tags: &[String]) -> bool {
+    name.strip_prefix("end").is_some_and(|opened| {
+        table.iter().any(|(tag, _)| *tag == opened) || paired_tags.iter().any(|tag| tag == opened)
+    })
+}
+
+/// Whether a `set` tag’s arguments assign a value, like `x = 1`, rather than
+/// capture the block’s contents, like `x|upper`.
+fn is_assignment(args: &[Cow<'_, str>]) -> bool {
+    let args = args.join(" ");
+    let target = args.split(['|', '(']).next().unwrap_or_default();
+    target.contains('=')
+}
+
+fn parent_of(block: &Block, positions: &[Position]) -> Option<usize> {
+    positions[block.start()].parent
+}
+
+fn push_node(stack: &mut [OpenBlock], root: &mut Vec<Node>, node: Node) {
+    match stack.last_mut() {
+        Some(open) => open.block.branches.last_mut().unwrap().children.push(node),
+        None => root.push(node),
+    }
+}
+
+fn collect_blocks<'t>(nodes: &'t [Node], blocks: &mut Vec<&'t Block>) {
+    for node in nodes {
+        if let Node::Block(block) = node {
+            blocks.push(block);
+            for branch in &block.branches {
+                collect_blocks(&branch.children, blocks);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{jinja, lex};
+
+    fn parse(template: &str) -> Tree {
+        Tree::parse(&lex(template))
+    }
+
+    fn depths(tree: &Tree) -> Vec<usize> {
+        tree.positions
+            .iter()
+            .map(|position| position.depth)
+            .collect()
+    }
+
+    fn parents(tree: &Tree) -> Vec<Option<usize>> {
+        tree.positions
+            .iter()
+            .map(|position| position.parent)
+            .collect()
+    }
+
+    #[test]
+    fn test_flat() {
+        let tree = parse("{% load static %}\n{{ a }}");
+        assert_eq!(tree.root, vec![Node::Leaf(0), Node::Leaf(1), Node::Leaf(2)]);
+        assert_eq!(depths(&tree), vec![0, 0, 0]);
+        assert_eq!(parents(&tree), vec![None, None, None]);
+    }
+
+    #[test]
+    fn test_if_elif_else() {
+        let tree = parse("{% if a %}1{% elif b %}2{% else %}3{% endif %}");
+        assert_eq!(
+            tree.root,
+            vec![Node::Block(Block {
+                branches: vec![
+                    Branch {
+                        tag: 0,
+                        children: vec![Node::Leaf(1)],
+                    },
+                    Branch {
+                        tag: 2,
+                        children: vec![Node::Leaf(3)],
+                    },
+                    Branch {
+                        tag: 4,
+                        children: vec![Node::Leaf(5)],
+                    },
+                ],
+                end: Some(6),
+            })]
+        );
+        assert_eq!(depths(&tree), vec![0, 1, 0, 1, 0, 1, 0]);
+        assert_eq!(
+            parents(&tree),
+            vec![None, Some(0), None, Some(0), None, Some(0), None]
+        );
+    }
+
+    #[test]
+    fn test_for_empty() {
+        let tree = parse("{% for a in b %}{{ a }}{% empty %}-{% endfor %}");
+        let blocks = tree.blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            blocks[0].branches.iter().map(|b| b.tag).collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+        assert_eq!(blocks[0].end, Some(4));
+    }
+
+    #[test]
+    fn test_nested() {
+        let tree =
+            parse("{% block a %}{% block b %}{% if c %}{% endif %}{% endblock %}{% endblock %}");
+        assert_eq!(depths(&tree), vec![0, 1, 2, 2, 1, 0]);
+        assert_eq!(
+            parents(&tree),
+            vec![None, Some(0), Some(1), Some(1), Some(0), None]
+        );
+        let blocks = tree.blocks();
+        assert_eq!(
+            blocks
+                .iter()
+                .map(|block| (block.start(), block.end))
+                .collect::<Vec<_>>(),
+            vec![(0, Some(5)), (1, Some(4)), (2, Some(3))]
+        );
+    }
+
+    #[test]
+    fn test_intermediate_outside_block() {
+        let tree = parse("{% else %}{% empty %}");
+        assert_eq!(tree.root, vec![Node::Leaf(0), Node::Leaf(1)]);
+    }
+
+    #[test]
+    fn test_intermediate_of_outer_block() {
+        // {% else %} belongs to the innermost open block only.
+        let tree = parse("{% if a %}{% with b=c %}{% else %}{% endwith %}{% endif %}");
+        let blocks = tree.blocks();
+        assert_eq!(blocks[0].branches.len(), 1);
+        assert_eq!(blocks[1].branches.len(), 1);
+        assert_eq!(blocks[1].branches[0].children, vec![Node::Leaf(2)]);
+    }
+
+    #[test]
+    fn test_unclosed() {
+        let tree = parse("{% block a %}{% if b %}");
+        let blocks = tree.blocks();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].end, None);
+        assert_eq!(blocks[1].end, None);
+        assert_eq!(depths(&tree), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_mismatched_close_leaves_inner_unclosed() {
+        let tree = parse("{% if a %}{% for b in c %}{% endif %}");
+        let blocks = tree.blocks();
+        assert_eq!(blocks[0].end, Some(2));
+        assert_eq!(blocks[1].end, None);
+    }
+
+    #[test]
+    fn test_unmatched_close() {
+        let tree = parse("{% endif %}{% endblock %}{% endcustom %}");
+        assert_eq!(tree.root, vec![Node::Leaf(0), Node::Leaf(1), Node::Leaf(2)]);
+    }
+
+    #[test]
+    fn test_verbatim() {
+        let tree = parse("{% verbatim %}{% if %}{% endverbatim %}");
+        let blocks = tree.blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].branches[0].children, vec![Node::Leaf(1)]);
+        assert_eq!(blocks[0].end, Some(2));
+    }
+
+    fn diagnostics(template: &str) -> Vec<String> {
+        parse(template)
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_diagnostics_none() {
+        assert!(
+            diagnostics("{% block a %}{% if b %}{% else %}{% endif %}{% endblock %}").is_empty()
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_unexpected_end() {
+        assert_eq!(
+            diagnostics("a\n  {% endblock %}"),
+            vec!["unexpected 'endblock' at line 2, column 3"]
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_unknown_intermediate_ignored() {
+        assert!(diagnostics("{% flag 'x' %}a{% else %}b{% endflag %}").is_empty());
+    }
+
+    #[test]
+    fn test_diagnostics_unknown_end_ignored() {
+        assert!(diagnostics("{% endcustom %}").is_empty());
+    }
+
+    #[test]
+    fn test_diagnostics_mismatched() {
+        assert_eq!(
+            diagnostics("{% ifequal a b %}\n{% if c %}\n{% endifequal %}"),
+            vec!["expected 'endif', found 'endifequal' at line 3, column 1"]
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_unclosed() {
+        assert_eq!(
+            diagnostics("{% block a %}\n{% for b in c %}{% endfor %}{% if d %}"),
+            vec![
+                "unclosed 'block' at line 1, column 1",
+                "unclosed 'if' at line 2, column 29",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_sorted() {
+        assert_eq!(
+            diagnostics("{% if a %}{% endfor %}"),
+            vec![
+                "unclosed 'if' at line 1, column 1",
+                "unexpected 'endfor' at line 1, column 11",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comment_contents() {
+        let tree = parse("{% comment %}{% if a %}{% endblock %}{% endcomment %}");
+        assert!(tree.diagnostics.is_empty());
+        let blocks = tree.blocks();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(
+            blocks[0].branches[0].children,
+            vec![Node::Leaf(1), Node::Leaf(2)]
+        );
+        assert_eq!(blocks[0].end, Some(3));
+    }
+
+    #[test]
+    fn test_paired_tags() {
+        let paired_tags = ["slot".to_string()];
+        let tree = Tree::parse_with_paired_tags(
+            &lex("{% slot a %}{% if b %}{% endif %}{% endslot %}{% endslot %}"),
+            &paired_tags,
+        );
+        assert_eq!(depths(&tree), vec![0, 1, 1, 0, 0]);
+        assert_eq!(tree.blocks()[0].end, Some(3));
+        assert_eq!(
+            tree.diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["unexpected 'endslot' at line 1, column 47"]
+        );
+    }
+
+    #[test]
+    fn test_paired_tags_intermediate() {
+        let paired_tags = ["flag".to_string()];
+        let tree = Tree::parse_with_paired_tags(
+            &lex("{% flag 'x' %}a{% else %}b{% endflag %}"),
+            &paired_tags,
+        );
+        assert_eq!(depths(&tree), vec![0, 1, 0, 1, 0]);
+        assert_eq!(tree.blocks()[0].branches.len(), 2);
+    }
+
+    #[test]
+    fn test_intermediate_tags_match_tables() {
+        for (table, intermediate_tags) in [
+            (PAIRED_TAGS, INTERMEDIATE_TAGS),
+            (JINJA2_PAIRED_TAGS, JINJA2_INTERMEDIATE_TAGS),
+        ] {
+            let mut expected: Vec<&str> = table
+                .iter()
+                .flat_map(|(_, intermediates)| intermediates.iter().copied())
+                .collect();
+            expected.sort_unstable();
+            expected.dedup();
+            assert_eq!(intermediate_tags, expected);
+        }
+    }
+
+    #[test]
+    fn test_paired_tags_not_configured() {
+        let tree = parse("{% slot a %}{% endslot %}");
+        assert_eq!(tree.root, vec![Node::Leaf(0), Node::Leaf(1)]);
+        assert!(tree.diagnostics.is_empty());
+    }
+
+    // Jinja2
+
+    fn parse_jinja2(template: &str) -> Tree {
+        Tree::parse_jinja2(&jinja::lex(template).unwrap(), &[])
+    }
+
+    #[test]
+    fn test_jinja2_paired_tags() {
+        let tree = parse_jinja2(
+            "{% macro m() %}{% call(x) n() %}{% endcall %}{% endmacro %}\
+             {% for a in b %}{% else %}{% endfor %}{% trans %}{% pluralize %}{% endtrans %}",
+        );
+        assert_eq!(depths(&tree), vec![0, 1, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(tree.blocks().len(), 4);
+        assert!(tree.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_jinja2_set() {
+        let tree = parse_jinja2("{% set a = 1 %}{% set b|upper %}{{ a }}{% endset %}");
+        assert_eq!(depths(&tree), vec![0, 0, 1, 0]);
+        assert_eq!(tree.blocks()[0].start(), 1);
+        assert!(tree.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_jinja2_django_tags_unpaired() {
+        let tree = parse_jinja2("{% for a in b %}{% empty %}{% endfor %}{% endverbatim %}");
+        assert_eq!(depths(&tree), vec![0, 1, 0, 0]);
+        assert!(tree.diagnostics.is_empty());
+    }
+}
@@ -0,0 +1,41 @@
+// WebAssembly bindings for JavaScript, built with the “wasm” feature.
+
+use crate::{FormatOptions, SUPPORTED_TARGET_VERSIONS, Version, check_str, format_str};
+use wasm_bindgen::prelude::*;
+
+fn format_options(target_version: Option<String>) -> Result<FormatOptions, JsError> {
+    let target_version = target_version
+        .map(|version_str| {
+            version_str
+                .parse::<Version>()
+                .map_err(|e| JsError::new(&format!("Invalid target version {version_str:?}: {e}")))
+        })
+        .transpose()?;
+    Ok(FormatOptions {
+        target_version,
+        ..FormatOptions::default()
+    })
+}
+
+/// Format a template string.
+#[wasm_bindgen(js_name = format)]
+pub fn format_js(text: &str, target_version: Option<String>) -> Result<String, JsError> {
+    let options = format_options(target_version)?;
+    format_str(text, &options).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Return whether a template string is already formatted.
+#[wasm_bindgen]
+pub fn check(text: &str, target_version: Option<String>) -> Result<bool, JsError> {
+    let options = format_options(target_version)?;
+    check_str(text, &options).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// The Django versions accepted as target versions.
+#[wasm_bindgen(js_name = supportedTargetVersions)]
+pub fn supported_target_versions() -> Vec<String> {
+    SUPPORTED_TARGET_VERSIONS
+        .iter()
+        .map(|version| version.to_string())
+        .collect()
+}
@@ -1,10 +1,16 @@
 use std::fs;
 use std::io::Write;
+use std::path::Path;
 use std::process::{Child, Command, Output, Stdio};
 use tempfile::tempdir;
 
 fn run_djade(args: &[&str]) -> Child {
+    run_djade_in(Path::new("."), args)
+}
+
+fn run_djade_in(dir: &Path, args: &[&str]) -> Child {
     let mut cmd = Command::new(env!("CARGO_BIN_EXE_djade"));
+    cmd.current_dir(dir);
     for arg in args {
         cmd.arg(arg);
     }
@@ -114,3 +120,236 @@ fn test_stdin_mixed_with_files() {
     let file_content = fs::read_to_string(&file_path).unwrap();
     assert_eq!(file_content, "{{ thomas }}\n");
 }
+
+#[test]
+fn test_show_config() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("test.html");
+    fs::write(&file_path, "{{ thomas }}\n").unwrap();
+
+    let child = run_djade(&[
+        "show-config",
+        "--target-version",
+        "5.2",
+        file_path.to_str().unwrap(),
+    ]);
+    let output = write_to_stdin_and_wait(child, b"");
+
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Target version: 5.2 (from --target-version)\n"));
+    assert!(stdout.contains("  length-is (Django 4.2+): active\n"));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn test_jinja2_extension() {
+    let dir = tempdir().unwrap();
+    let file_path = dir.path().join("engine.html.j2");
+    fs::write(
+        &file_path,
+        "{%-block  funnel%}\n{{  load(  'coal' )  -}}\n{%endblock-%}\n",
+    )
+    .unwrap();
+
+    let child = run_djade(&[file_path.to_str().unwrap()]);
+    let output = write_to_stdin_and_wait(child, b"");
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "1 file reformatted\n"
+    );
+    assert_eq!(
+        fs::read_to_string(&file_path).unwrap(),
+        "{%- block funnel %}\n{{ load( 'coal' ) -}}\n{% endblock funnel -%}\n"
+    );
+}
+
+#[test]
+fn test_dialect_jinja2_stdin() {
+    let child = run_djade(&["--dialect", "jinja2", "-"]);
+    let output = write_to_stdin_and_wait(child, b"{% raw %}{{x}}{% endraw %}{{  y|e  }}");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{% raw %}{{x}}{% endraw %}{{ y|e }}\n"
+    );
+}
+
+#[test]
+fn test_check_third_party_intermediate_tag() {
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("t.html"),
+        "{% load waffle_tags %}\n{% flag \"x\" %}\n  a\n{% else %}\n  b\n{% endflag %}\n",
+    )
+    .unwrap();
+
+    let child = run_djade_in(
+        dir.path(),
+        &["--check", "--target-version", "5.2", "t.html"],
+    );
+    let output = child.wait_with_output().expect("Failed to read output");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "1 file already formatted\n"
+    );
+}
+
+#[test]
+fn test_pyproject_paired_tags() {
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("pyproject.toml"),
+        "[tool.djade]\npaired-tags = [\"slot\"]\n",
+    )
+    .unwrap();
+
+    let child = run_djade_in(dir.path(), &["-"]);
+    let output = write_to_stdin_and_wait(child, b"{% slot funnel %}\n{% endslot %}\n");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{% slot funnel %}\n{% endslot funnel %}\n"
+    );
+}
+
+#[test]
+fn test_pyproject_paired_tags_intermediate_check() {
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("pyproject.toml"),
+        "[tool.djade]\npaired-tags = [\"flag\"]\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("t.html"),
+        "{% load waffle_tags %}\n{% flag \"x\" %}\n  a\n{% else %}\n  b\n{% endflag \"x\" %}\n",
+    )
+    .unwrap();
+
+    let child = run_djade_in(
+        dir.path(),
+        &["--check", "--target-version", "5.2", "t.html"],
+    );
+    let output = child.wait_with_output().expect("Failed to read output");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "1 file already formatted\n"
+    );
+}
+
+#[test]
+fn test_pyproject_paired_tags_intermediate_format() {
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("pyproject.toml"),
+        "[tool.djade]\npaired-tags = [\"flag\"]\n",
+    )
+    .unwrap();
+
+    let child = run_djade_in(dir.path(), &["--target-version", "5.2", "-"]);
+    let output = write_to_stdin_and_wait(
+        child,
+        b"{% flag \"x\" %}\n  a\n{% else %}\n  b\n{% endflag %}\n",
+    );
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{% flag \"x\" %}\n  a\n{% else %}\n  b\n{% endflag \"x\" %}\n"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "1 file reformatted\n"
+    );
+}
+
+#[test]
+fn test_pyproject_quote_style() {
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("pyproject.toml"),
+        "[tool.djade]\nquote-style = \"double\"\n",
+    )
+    .unwrap();
+
+    let child = run_djade_in(dir.path(), &["-"]);
+    let output = write_to_stdin_and_wait(
+        child,
+        b"{% include 'shed.html' %}{{ name|default:'Percy' }}\n",
+    );
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "{% include \"shed.html\" %}{{ name|default:\"Percy\" }}\n"
+    );
+}
+
+#[test]
+fn test_pyproject_invalid_config() {
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("pyproject.toml"),
+        "[tool.djade]\npaired-tags = [\"\"]\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("thomas.html"), "{{ thomas }}\n").unwrap();
+
+    let child = run_djade_in(dir.path(), &["thomas.html"]);
+    let output = child.wait_with_output().expect("Failed to read output");
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error: invalid configuration: tool.djade.paired-tags in pyproject.toml: invalid tag name \"\"\n"
+    );
+}
+
+#[test]
+fn test_show_config_paired_tags() {
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("pyproject.toml"),
+        "[tool.djade]\npaired-tags = [\"component\"]\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("test.html"), "{{ thomas }}\n").unwrap();
+
+    let child = run_djade_in(dir.path(), &["show-config", "test.html"]);
+    let output = write_to_stdin_and_wait(child, b"");
+
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Paired tags (from pyproject.toml):\n  component ... endcomponent\n"));
+}
+
+#[test]
+fn test_show_config_quote_style() {
+    let dir = tempdir().unwrap();
+    fs::write(
+        dir.path().join("pyproject.toml"),
+        "[tool.djade]\nquote-style = \"single\"\n",
+    )
+    .unwrap();
+    fs::write(dir.path().join("test.html"), "{{ thomas }}\n").unwrap();
+
+    let child = run_djade_in(dir.path(), &["show-config", "test.html"]);
+    let output = write_to_stdin_and_wait(child, b"");
+
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  if-expressions\n  quote-style\n  endblock-labels\n"));
+    assert!(stdout.contains("Quote style (from pyproject.toml): single\n"));
+}